@@ -0,0 +1,386 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{Board, Element, Game, Mcts, Move, Outcome, State};
+
+const WIN_SCORE: i32 = 1_000_000;
+const HEURISTIC_SCALE: f32 = 10_000.0;
+
+/// One of the computer players.
+#[derive(Debug, Clone, Copy)]
+pub enum Engine {
+    AlphaBeta(AlphaBeta),
+    Mcts(Mcts),
+}
+
+impl Engine {
+    pub fn choose_move(&self, game: &Game) -> Option<Move> {
+        self.choose_move_with_stop(game, &AtomicBool::new(false))
+    }
+
+    /// Like [`choose_move`](Self::choose_move), but gives up early once `stop` is set.
+    pub fn choose_move_with_stop(&self, game: &Game, stop: &AtomicBool) -> Option<Move> {
+        match self {
+            Engine::AlphaBeta(alpha_beta) => alpha_beta.search_with_stop(game, stop).best_move,
+            Engine::Mcts(mcts) => mcts.search_with_stop(game, stop).best_move,
+        }
+    }
+}
+
+/// Engines are written as their name, followed by the settings that differ from the default:
+/// `alphabeta`, `alphabeta:nodes=20000,depth=6`, `alphabeta:movetime=500` or
+/// `mcts:playouts=5000,threads=4,seed=1`.
+impl std::fmt::Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut settings = vec![];
+        match self {
+            Engine::AlphaBeta(alpha_beta) => {
+                write!(f, "alphabeta")?;
+                let default = AlphaBeta::default();
+                if alpha_beta.budget != default.budget {
+                    settings.push(match alpha_beta.budget {
+                        SearchBudget::Time(time) => format!("movetime={}", time.as_millis()),
+                        SearchBudget::Nodes(nodes) => format!("nodes={nodes}"),
+                    });
+                }
+                if alpha_beta.max_depth != default.max_depth {
+                    settings.push(format!("depth={}", alpha_beta.max_depth));
+                }
+            }
+            Engine::Mcts(mcts) => {
+                write!(f, "mcts")?;
+                let default = Mcts::default();
+                if mcts.playouts != default.playouts {
+                    settings.push(format!("playouts={}", mcts.playouts));
+                }
+                if mcts.threads != default.threads {
+                    settings.push(format!("threads={}", mcts.threads));
+                }
+                if mcts.seed != default.seed {
+                    settings.push(format!("seed={}", mcts.seed));
+                }
+            }
+        }
+        if !settings.is_empty() {
+            write!(f, ":{}", settings.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEngineError(pub String);
+
+impl std::fmt::Display for ParseEngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid engine \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for ParseEngineError {}
+
+impl std::str::FromStr for Engine {
+    type Err = ParseEngineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseEngineError(s.to_string());
+        let (name, settings) = s.split_once(':').unwrap_or((s, ""));
+        let mut engine = match name {
+            "alphabeta" => Engine::AlphaBeta(AlphaBeta::default()),
+            "mcts" => Engine::Mcts(Mcts::default()),
+            _ => return Err(error()),
+        };
+        for setting in settings.split(',').filter(|setting| !setting.is_empty()) {
+            let (key, value) = setting.split_once('=').ok_or_else(error)?;
+            let value: u64 = value.parse().map_err(|_| error())?;
+            match (&mut engine, key) {
+                (Engine::AlphaBeta(alpha_beta), "movetime") => {
+                    alpha_beta.budget = SearchBudget::Time(Duration::from_millis(value))
+                }
+                (Engine::AlphaBeta(alpha_beta), "nodes") => {
+                    alpha_beta.budget = SearchBudget::Nodes(value)
+                }
+                (Engine::AlphaBeta(alpha_beta), "depth") => alpha_beta.max_depth = value as usize,
+                // a search without playouts or threads finds no move
+                (Engine::Mcts(_), "playouts" | "threads") if value == 0 => return Err(error()),
+                (Engine::Mcts(mcts), "playouts") => mcts.playouts = value,
+                (Engine::Mcts(mcts), "threads") => mcts.threads = value as usize,
+                (Engine::Mcts(mcts), "seed") => mcts.seed = value,
+                _ => return Err(error()),
+            }
+        }
+        Ok(engine)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchBudget {
+    Time(Duration),
+    Nodes(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// The score of the best move for the player to move, positive is good for them.
+    pub score: i32,
+    /// The deepest search that was completed.
+    pub depth: usize,
+    pub nodes: u64,
+}
+
+/// Iterative deepening alpha-beta search, stopping once the budget runs out and playing the best
+/// move of the deepest search that was completed.
+///
+/// The search takes turns between two players that each lose what the other wins, so it only
+/// plays games of two players. [`Mcts`] plays games with more.
+#[derive(Debug, Clone, Copy)]
+pub struct AlphaBeta {
+    pub budget: SearchBudget,
+    pub max_depth: usize,
+}
+
+impl Default for AlphaBeta {
+    fn default() -> Self {
+        Self {
+            budget: SearchBudget::Time(Duration::from_secs(1)),
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+impl AlphaBeta {
+    pub fn search(&self, game: &Game) -> SearchResult {
+        self.search_with_stop(game, &AtomicBool::new(false))
+    }
+
+    /// Like [`search`](Self::search), but also stops once `stop` is set, as if the budget had
+    /// run out.
+    pub fn search_with_stop(&self, game: &Game, stop: &AtomicBool) -> SearchResult {
+        let mut game = game.clone();
+        let mut search = Search {
+            budget: self.budget,
+            stop,
+            start: Instant::now(),
+            nodes: 0,
+            aborted: false,
+        };
+
+        let mut result = SearchResult {
+            best_move: game.legal_moves().into_iter().next(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        };
+        let max_depth = self.max_depth.min(game.num_moves_left());
+        for depth in 1..=max_depth {
+            let (score, best_move) = search.root(&mut game, depth, result.best_move.as_ref());
+            if search.aborted {
+                break;
+            }
+            result = SearchResult {
+                best_move,
+                score,
+                depth,
+                nodes: search.nodes,
+            };
+            if score.abs() > WIN_SCORE / 2 {
+                // the game has been solved, searching deeper won't change anything
+                break;
+            }
+        }
+        result.nodes = search.nodes;
+        result
+    }
+}
+
+struct Search<'a> {
+    budget: SearchBudget,
+    stop: &'a AtomicBool,
+    start: Instant,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl Search<'_> {
+    fn root(&mut self, game: &mut Game, depth: usize, first: Option<&Move>) -> (i32, Option<Move>) {
+        let mut moves = game.legal_moves();
+        if let Some(index) = first.and_then(|first| moves.iter().position(|mv| mv == first)) {
+            moves.swap(0, index);
+        }
+
+        let mut alpha = -WIN_SCORE;
+        let mut best_move = None;
+        for mv in moves {
+            game.play(&mv).unwrap();
+            let score = -self.negamax(game, depth - 1, -WIN_SCORE, -alpha, 1);
+            game.undo();
+            if self.aborted {
+                break;
+            }
+            if best_move.is_none() || score > alpha {
+                alpha = score;
+                best_move = Some(mv);
+            }
+        }
+        (alpha, best_move)
+    }
+
+    fn negamax(
+        &mut self,
+        game: &mut Game,
+        depth: usize,
+        mut alpha: i32,
+        beta: i32,
+        ply: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.nodes & 1023 == 0 {
+            self.aborted = self.stop.load(Ordering::Relaxed)
+                || match self.budget {
+                    SearchBudget::Time(time) => self.start.elapsed() >= time,
+                    SearchBudget::Nodes(nodes) => self.nodes >= nodes,
+                };
+        }
+        if self.aborted {
+            return 0;
+        }
+
+        match game.outcome() {
+            Some(Outcome::Winner(winner)) if winner == game.current_player() => {
+                return WIN_SCORE - ply
+            }
+            Some(Outcome::Winner(_)) => return -(WIN_SCORE - ply),
+            Some(Outcome::Stalemate) => return 0,
+            None => {}
+        }
+
+        if depth == 0 {
+            return (evaluate(game.board(), game.current_player()) * HEURISTIC_SCALE) as i32;
+        }
+
+        for mv in game.legal_moves() {
+            game.play(&mv).unwrap();
+            let score = -self.negamax(game, depth - 1, -beta, -alpha, ply + 1);
+            game.undo();
+            if self.aborted {
+                return 0;
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        alpha
+    }
+}
+
+/// How good `board` looks for `player`, from -1 when it is lost to 1 when it is won.
+///
+/// Every line that the opponent has not blocked counts for the progress made on it, where
+/// sub-boards that are not decided yet count for how good they look themselves. All other players
+/// count as the one opponent, since [`AlphaBeta`] only plays games of two players.
+pub fn evaluate(board: &Board, player: State) -> f32 {
+    if let Some(winner) = board.get_winner() {
+        return if winner == player { 1.0 } else { -1.0 };
+    }
+
+    // None for elements that are drawn, and so block lines for both players
+    let values: Vec<Vec<Option<f32>>> = board
+        .elements()
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|element| match element {
+                    &Element::State(Some(state)) | &Element::Won(state, _) => {
+                        Some(if state == player { 1.0 } else { -1.0 })
+                    }
+                    Element::State(None) => Some(0.0),
+                    Element::Board(board) if board.is_stalemate() => None,
+                    Element::Board(board) => Some(evaluate(board, player)),
+                })
+                .collect()
+        })
+        .collect();
+
+    // scaled so that a line counts as much as on a 3x3 board, whatever its length
+    let win_length = board.shape().win_length as f32;
+    let mut score = 0.0;
+    for line in board.shape().lines() {
+        let Some(values) = line
+            .cells()
+            .map(|(x, y)| values[x][y])
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        if values.iter().all(|&value| value > -1.0) {
+            let progress: f32 = values.iter().map(|value| value.max(0.0)).sum();
+            score += (3.0 * progress / win_length).powi(2);
+        }
+        if values.iter().all(|&value| value < 1.0) {
+            let progress: f32 = values.iter().map(|value| (-value).max(0.0)).sum();
+            score -= (3.0 * progress / win_length).powi(2);
+        }
+    }
+    if board.rules().misere {
+        // lines lose in misère
+        score = -score;
+    }
+    (score / 18.0).clamp(-0.95, 0.95)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_winning_move() {
+        let mut game = Game::new(1);
+        for mv in ["1", "4", "2", "5"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        let result = AlphaBeta {
+            budget: SearchBudget::Nodes(100_000),
+            max_depth: 3,
+        }
+        .search(&game);
+        assert_eq!(result.best_move, Some("3".parse().unwrap()));
+        assert!(result.score > WIN_SCORE / 2);
+    }
+
+    #[test]
+    fn engine_names() {
+        for name in [
+            "alphabeta",
+            "alphabeta:nodes=20000,depth=6",
+            "mcts:playouts=500,seed=3",
+        ] {
+            assert_eq!(name.parse::<Engine>().unwrap().to_string(), name);
+        }
+        assert_eq!(
+            "alphabeta:movetime=1000"
+                .parse::<Engine>()
+                .unwrap()
+                .to_string(),
+            "alphabeta"
+        );
+        assert!("mcts:depth=3".parse::<Engine>().is_err());
+        assert!("mcts:playouts=0".parse::<Engine>().is_err());
+        assert!("mcts:threads=0".parse::<Engine>().is_err());
+        assert!("minimax".parse::<Engine>().is_err());
+    }
+
+    #[test]
+    fn stops_at_the_node_budget() {
+        let game = Game::new(2);
+        let result = AlphaBeta {
+            budget: SearchBudget::Nodes(5_000),
+            max_depth: usize::MAX,
+        }
+        .search(&game);
+        assert!(result.best_move.is_some());
+        assert!(result.nodes < 5_000 + 1024);
+    }
+}
@@ -0,0 +1,970 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+use eframe::egui;
+use encase::ShaderType;
+
+use crate::{
+    board_to_svg, render_heatmap, today, AlphaBeta, BoardMesh, BoardShape, CellPath, Engine, Game,
+    GameRecord, LoadError, Mcts, MctsResult, Move, NetEvent, NetSession, Outcome, PerObjectData,
+    PlayerStyle, Position, Rules, SaveFile, SearchBudget, State, Symbol, WindowRenderState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, ShaderType)]
+pub struct Camera {
+    pub position: cgmath::Vector2<f32>,
+    pub screen_size: cgmath::Vector2<f32>,
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+pub struct App {
+    camera: Camera,
+    last_frame_time: std::time::Instant,
+    game: Game,
+    game_over: bool,
+    num_layers: usize,
+    shape: BoardShape,
+    /// The rules for the next game, the current one keeps the rules it started with.
+    rules: Rules,
+    early_draws: bool,
+    vs_computer: bool,
+    computer_player: State,
+    computer_engine: EngineKind,
+    computer_think_time: f32,
+    mcts: Mcts,
+    computer_search: Option<BackgroundSearch<Option<Move>>>,
+    show_heatmap: bool,
+    heatmap: Option<BackgroundSearch<MctsResult>>,
+    /// Draws the board with several samples per pixel for smoother edges.
+    msaa: bool,
+    save_path: String,
+    position_text: String,
+    status: Option<String>,
+    board_mesh: BoardMesh,
+    /// The objects of the board and the heatmap, shared with the paint callback.
+    per_object_data: Arc<Vec<PerObjectData>>,
+    /// Changes whenever the objects do.
+    per_object_data_version: u64,
+    /// Indexed by player.
+    player_styles: [PlayerStyle; 4],
+    network: Option<NetSession>,
+    network_address: String,
+    /// The player this side plays when hosting.
+    network_player: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EngineKind {
+    AlphaBeta,
+    Mcts,
+}
+
+impl EngineKind {
+    fn name(self) -> &'static str {
+        match self {
+            EngineKind::AlphaBeta => "alpha-beta",
+            EngineKind::Mcts => "mcts",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [EngineKind::AlphaBeta, EngineKind::Mcts]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+/// Where the last game is kept between runs by eframe.
+const STORAGE_KEY: &str = "game";
+
+/// A search that is running on another thread so the ui stays responsive.
+struct BackgroundSearch<T> {
+    /// The [`start_version`](Game::start_version) and [`version`](Game::version) of the game the
+    /// search was started for, so stale results can be thrown away.
+    versions: (u64, u64),
+    receiver: mpsc::Receiver<T>,
+    result: Option<T>,
+    /// Tells the search to give up, once nobody waits for it anymore.
+    stop: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> BackgroundSearch<T> {
+    fn spawn(game: &Game, search: impl FnOnce(&Game, &AtomicBool) -> T + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let versions = (game.start_version(), game.version());
+        let stop = Arc::new(AtomicBool::new(false));
+        let game = game.clone();
+        std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                // the receiver is gone if the game changed while searching
+                let _ = sender.send(search(&game, &stop));
+            }
+        });
+        Self {
+            versions,
+            receiver,
+            result: None,
+            stop,
+        }
+    }
+
+    fn is_for(&self, game: &Game) -> bool {
+        self.versions == (game.start_version(), game.version())
+    }
+
+    fn poll(&mut self) -> Option<&T> {
+        if self.result.is_none() {
+            self.result = self.receiver.try_recv().ok();
+        }
+        self.result.as_ref()
+    }
+}
+
+impl<T> Drop for BackgroundSearch<T> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl App {
+    /// Opens the game saved at `path`, or the game from the last run if there is none.
+    pub fn new(cc: &eframe::CreationContext<'_>, path: Option<std::path::PathBuf>) -> Self {
+        let camera = Camera {
+            position: (0.0, 0.0).into(),
+            screen_size: (1.0, 1.0).into(),
+            rotation: cgmath::Rad::from(cgmath::Deg(0.0)).0,
+            scale: 0.5,
+        };
+
+        let wgpu_render_state = cc.wgpu_render_state.as_ref().unwrap();
+
+        let render_state =
+            WindowRenderState::new(&wgpu_render_state.device, wgpu_render_state.target_format);
+        wgpu_render_state
+            .renderer
+            .write()
+            .paint_callback_resources
+            .insert(render_state);
+
+        let num_layers = 2;
+        let mut app = Self {
+            camera,
+            last_frame_time: std::time::Instant::now(),
+            game: Game::new(num_layers),
+            game_over: false,
+            num_layers,
+            shape: BoardShape::default(),
+            rules: Rules::default(),
+            early_draws: false,
+            vs_computer: false,
+            computer_player: State::Cross,
+            computer_engine: EngineKind::AlphaBeta,
+            computer_think_time: 1.0,
+            mcts: Mcts {
+                threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+                ..Default::default()
+            },
+            computer_search: None,
+            show_heatmap: false,
+            heatmap: None,
+            msaa: false,
+            save_path: "game.uttt".to_string(),
+            position_text: String::new(),
+            status: None,
+            board_mesh: BoardMesh::default(),
+            per_object_data: Arc::default(),
+            per_object_data_version: 0,
+            player_styles: State::ALL.map(PlayerStyle::default_for),
+            network: None,
+            network_address: "127.0.0.1:7878".to_string(),
+            network_player: State::Circle,
+        };
+
+        if let Some(path) = path {
+            app.save_path = path.display().to_string();
+            if let Err(error) = app.load_from_file() {
+                app.status = Some(format!("Could not load {}: {error}", app.save_path));
+            }
+        } else if let Some(save_file) = cc
+            .storage
+            .and_then(|storage| storage.get_string(STORAGE_KEY))
+            .and_then(|text| text.parse::<SaveFile>().ok())
+        {
+            // a game from an older version that can't be replayed anymore is not worth an error
+            let _ = app.load_save_file(&save_file);
+        }
+        app
+    }
+
+    fn to_save_file(&self) -> SaveFile {
+        let mut save_file = SaveFile::from_game(&self.game);
+        save_file.set_setting("early_draws", self.early_draws);
+        save_file.set_setting("vs_computer", self.vs_computer);
+        save_file.set_setting("computer_player", self.computer_player);
+        save_file.set_setting("computer_engine", self.computer_engine.name());
+        save_file.set_setting("computer_think_time", self.computer_think_time);
+        save_file.set_setting("mcts_playouts", self.mcts.playouts);
+        save_file.set_setting("mcts_threads", self.mcts.threads);
+        save_file.set_setting("mcts_seed", self.mcts.seed);
+        save_file.set_setting("show_heatmap", self.show_heatmap);
+        save_file.set_setting("msaa", self.msaa);
+        for player in State::ALL {
+            let style = self.player_styles[player as usize];
+            let [r, g, b] = style.color;
+            save_file.set_setting(&format!("symbol_{player}"), style.symbol.name());
+            save_file.set_setting(&format!("color_{player}"), format!("{r} {g} {b}"));
+        }
+        save_file
+    }
+
+    fn load_save_file(&mut self, save_file: &SaveFile) -> Result<(), LoadError> {
+        self.set_game(save_file.to_game()?);
+
+        fn load_setting<T: std::str::FromStr>(save_file: &SaveFile, name: &str, value: &mut T) {
+            if let Some(setting) = save_file.setting(name).and_then(|s| s.parse().ok()) {
+                *value = setting;
+            }
+        }
+        load_setting(save_file, "early_draws", &mut self.early_draws);
+        self.game.set_early_draws(self.early_draws);
+        load_setting(save_file, "vs_computer", &mut self.vs_computer);
+        load_setting(save_file, "computer_player", &mut self.computer_player);
+        if let Some(engine) = save_file
+            .setting("computer_engine")
+            .and_then(EngineKind::from_name)
+        {
+            self.computer_engine = engine;
+        }
+        load_setting(
+            save_file,
+            "computer_think_time",
+            &mut self.computer_think_time,
+        );
+        load_setting(save_file, "mcts_playouts", &mut self.mcts.playouts);
+        load_setting(save_file, "mcts_threads", &mut self.mcts.threads);
+        load_setting(save_file, "mcts_seed", &mut self.mcts.seed);
+        load_setting(save_file, "show_heatmap", &mut self.show_heatmap);
+        load_setting(save_file, "msaa", &mut self.msaa);
+        for player in State::ALL {
+            let style = &mut self.player_styles[player as usize];
+            if let Some(symbol) = save_file
+                .setting(&format!("symbol_{player}"))
+                .and_then(Symbol::from_name)
+            {
+                style.symbol = symbol;
+            }
+            let color = save_file
+                .setting(&format!("color_{player}"))
+                .and_then(|color| {
+                    let channels: Vec<f32> = color
+                        .split(' ')
+                        .map(|channel| channel.parse().ok())
+                        .collect::<Option<_>>()?;
+                    channels.try_into().ok()
+                });
+            if let Some(color) = color {
+                style.color = color;
+            }
+        }
+        Ok(())
+    }
+
+    fn save_to_file(&self) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(&self.save_path, self.to_save_file().to_string())?;
+        Ok(())
+    }
+
+    fn load_from_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let save_file: SaveFile = std::fs::read_to_string(&self.save_path)?.parse()?;
+        self.load_save_file(&save_file)?;
+        Ok(())
+    }
+
+    fn to_record(&self) -> GameRecord {
+        let mut record = GameRecord::from_game(&self.game);
+        if self.vs_computer {
+            for &player in self.game.rules().players() {
+                let name = if player == self.computer_player {
+                    format!("Computer ({})", self.computer_engine.name())
+                } else {
+                    "Human".to_string()
+                };
+                record.set_tag(&player.to_string(), name);
+            }
+        }
+        record.set_tag("Date", today());
+        record
+    }
+
+    fn export_record(&self) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(&self.save_path, self.to_record().to_string())?;
+        Ok(())
+    }
+
+    /// Writes the board next to the save file, as an SVG image that is sharp at any zoom.
+    fn export_svg(&self) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+        let path = std::path::Path::new(&self.save_path).with_extension("svg");
+        let svg = board_to_svg(
+            self.game.board(),
+            &self.game.target_board(),
+            &self.player_styles,
+            1024,
+        );
+        std::fs::write(&path, svg)?;
+        Ok(path)
+    }
+
+    fn import_record(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let record: GameRecord = std::fs::read_to_string(&self.save_path)?.parse()?;
+        self.set_game(record.replay()?);
+        Ok(())
+    }
+
+    fn set_game(&mut self, mut game: Game) {
+        game.set_early_draws(self.early_draws);
+        self.num_layers = game.num_layers();
+        self.shape = game.shape();
+        self.rules = game.rules();
+        self.game = game;
+    }
+
+    fn restart(&mut self) {
+        let mut game = Game::with_shape(self.num_layers, self.shape);
+        game.set_rules(self.rules);
+        self.set_game(game);
+    }
+
+    /// Whether the player to move is played on this machine.
+    fn is_local_turn(&self) -> bool {
+        match &self.network {
+            Some(network) => network.is_local_turn(&self.game),
+            None => true,
+        }
+    }
+
+    fn is_computer_turn(&self) -> bool {
+        self.vs_computer
+            && self.is_local_turn()
+            && self.game.outcome().is_none()
+            && self.game.current_player() == self.computer_player
+    }
+
+    fn update_computer(&mut self) {
+        // alpha-beta only plays games of two players
+        if self.game.rules().num_players > 2 {
+            self.computer_engine = EngineKind::Mcts;
+        }
+        if !self.is_computer_turn() {
+            self.computer_search = None;
+            return;
+        }
+
+        match &mut self.computer_search {
+            Some(search) if search.is_for(&self.game) => {
+                if let Some(mv) = search.poll().cloned() {
+                    self.computer_search = None;
+                    if let Some(mv) = mv {
+                        // an illegal move leaves the game as it is, so the next frame searches
+                        // again
+                        let _ = self.game.play(&mv);
+                    }
+                }
+            }
+            _ => {
+                let engine = match self.computer_engine {
+                    EngineKind::AlphaBeta => Engine::AlphaBeta(AlphaBeta {
+                        budget: SearchBudget::Time(std::time::Duration::from_secs_f32(
+                            self.computer_think_time,
+                        )),
+                        ..Default::default()
+                    }),
+                    EngineKind::Mcts => Engine::Mcts(self.mcts),
+                };
+                self.computer_search =
+                    Some(BackgroundSearch::spawn(&self.game, move |game, stop| {
+                        engine.choose_move_with_stop(game, stop)
+                    }));
+            }
+        }
+    }
+
+    fn update_network(&mut self) {
+        let Some(network) = &mut self.network else {
+            return;
+        };
+        for event in network.update(&mut self.game) {
+            match event {
+                NetEvent::NewGame(game) => self.set_game(*game),
+                event => self.status = Some(event.to_string()),
+            }
+        }
+    }
+
+    fn update_heatmap(&mut self) {
+        if !self.show_heatmap || self.game.outcome().is_some() {
+            self.heatmap = None;
+            return;
+        }
+
+        if !matches!(&self.heatmap, Some(heatmap) if heatmap.is_for(&self.game)) {
+            let mcts = self.mcts;
+            self.heatmap = Some(BackgroundSearch::spawn(&self.game, move |game, stop| {
+                mcts.search_with_stop(game, stop)
+            }));
+        }
+    }
+
+    /// Brings the objects that are drawn up to date, with a new version if they changed.
+    fn update_per_object_data(&mut self) {
+        let board_changed = self
+            .board_mesh
+            .update(&self.game, &self.player_styles, &self.camera);
+        let mut heatmap = vec![];
+        if let Some(result) = self.heatmap.as_mut().and_then(|heatmap| heatmap.poll()) {
+            let size = self.game.board().shape().size;
+            render_heatmap(&result.stats, size, &mut heatmap);
+        }
+        if !board_changed {
+            // the heatmap has few objects, so it is just compared with the last one
+            let old_heatmap = &self.per_object_data[self.board_mesh.data().len()..];
+            if bytemuck::cast_slice::<_, u8>(old_heatmap) == bytemuck::cast_slice::<_, u8>(&heatmap)
+            {
+                return;
+            }
+        }
+
+        let mut per_object_data = self.board_mesh.data().to_vec();
+        per_object_data.extend(heatmap);
+        self.per_object_data = Arc::new(per_object_data);
+        self.per_object_data_version += 1;
+    }
+}
+
+impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        storage.set_string(STORAGE_KEY, self.to_save_file().to_string());
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let time = std::time::Instant::now();
+        let ts = time.duration_since(self.last_frame_time).as_secs_f32();
+        self.last_frame_time = time;
+
+        // maybe not do this all the time? only do it when the camera is moving or smth
+        ctx.request_repaint();
+
+        if !ctx.wants_keyboard_input() {
+            ctx.input_mut(|i| {
+                if i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z) {
+                    self.game.undo();
+                }
+                if i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y) {
+                    self.game.redo();
+                }
+            });
+        }
+
+        self.update_network();
+        self.update_computer();
+        self.update_heatmap();
+
+        egui::SidePanel::left("Settings").show(ctx, |ui| {
+            ui.label(format!("Current Turn: {}", self.game.current_player()));
+            ui.label(format!("Board to play in: {}", self.game.target_board()));
+            ui.label(format!("Number of moves: {}", self.game.num_moves()));
+            ui.label(format!(
+                "Number of possible moves left: {}",
+                self.game.num_moves_left()
+            ));
+            ui.label(format!("Number of layers: {}", self.num_layers));
+            ui.horizontal(|ui| {
+                let can_add = self.shape.fits(self.num_layers + 1);
+                if ui
+                    .add_enabled(can_add, egui::Button::new("Add Layer"))
+                    .clicked()
+                {
+                    self.num_layers += 1;
+                    self.restart();
+                }
+                if ui.button("Remove Layer").clicked() && self.num_layers > 1 {
+                    self.num_layers -= 1;
+                    self.restart();
+                }
+            });
+            // bigger boards with this many layers would not fit in memory
+            let max_size = (3..=6)
+                .rev()
+                .find(|&size| BoardShape { size, ..self.shape }.fits(self.num_layers))
+                .unwrap_or(3);
+            let size_changed = ui
+                .add(egui::Slider::new(&mut self.shape.size, 3..=max_size).text("Board size"))
+                .changed();
+            self.shape.win_length = self.shape.win_length.min(self.shape.size);
+            let win_length_changed = ui
+                .add(
+                    egui::Slider::new(&mut self.shape.win_length, 3..=self.shape.size)
+                        .text("In a row to win"),
+                )
+                .changed();
+            if size_changed || win_length_changed {
+                self.restart();
+            }
+            ui.collapsing(format!("Rules: {}", self.game.rules()), |ui| {
+                ui.add(egui::Slider::new(&mut self.rules.num_players, 2..=4).text("Players"));
+                ui.checkbox(&mut self.rules.misere, "Misère, a line loses");
+                ui.checkbox(
+                    &mut self.rules.play_in_won_boards,
+                    "Keep playing in won boards",
+                );
+                ui.checkbox(&mut self.rules.wild_draws, "Drawn boards count for both");
+                ui.checkbox(
+                    &mut self.rules.majority_tiebreak,
+                    "Most boards wins a stalemate",
+                );
+                if self.rules != self.game.rules() {
+                    ui.label("The rules change when the game is reset");
+                }
+            });
+            ui.collapsing("Players", |ui| {
+                for &player in self.game.rules().players() {
+                    let style = &mut self.player_styles[player as usize];
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source(format!("symbol_{player}"))
+                            .selected_text(style.symbol.name())
+                            .show_ui(ui, |ui| {
+                                for symbol in Symbol::ALL {
+                                    ui.selectable_value(&mut style.symbol, symbol, symbol.name());
+                                }
+                            });
+                        ui.color_edit_button_rgb(&mut style.color);
+                        ui.label(player.to_string());
+                    });
+                }
+            });
+            ui.checkbox(&mut self.msaa, "Smoother edges with multisampling");
+            if ui.button("Reset").clicked() {
+                self.restart();
+            }
+            if ui
+                .checkbox(&mut self.early_draws, "End the game once nobody can win")
+                .changed()
+            {
+                self.game.set_early_draws(self.early_draws);
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.text_edit_singleline(&mut self.save_path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.status = Some(match self.save_to_file() {
+                        Ok(()) => format!("Saved to {}", self.save_path),
+                        Err(error) => format!("Could not save {}: {error}", self.save_path),
+                    });
+                }
+                if ui.button("Load").clicked() {
+                    self.status = Some(match self.load_from_file() {
+                        Ok(()) => format!("Loaded {}", self.save_path),
+                        Err(error) => format!("Could not load {}: {error}", self.save_path),
+                    });
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Export Record").clicked() {
+                    self.status = Some(match self.export_record() {
+                        Ok(()) => format!("Exported the record to {}", self.save_path),
+                        Err(error) => format!("Could not export {}: {error}", self.save_path),
+                    });
+                }
+                if ui.button("Import Record").clicked() {
+                    self.status = Some(match self.import_record() {
+                        Ok(()) => format!("Imported the record from {}", self.save_path),
+                        Err(error) => format!("Could not import {}: {error}", self.save_path),
+                    });
+                }
+                if ui.button("Copy Record").clicked() {
+                    let record = self.to_record().to_string();
+                    ctx.output_mut(|output| output.copied_text = record);
+                    self.status = Some("Copied the record".to_string());
+                }
+            });
+            if ui.button("Export SVG").clicked() {
+                self.status = Some(match self.export_svg() {
+                    Ok(path) => format!("Exported the board to {}", path.display()),
+                    Err(error) => format!("Could not export the board: {error}"),
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label("Position:");
+                ui.text_edit_singleline(&mut self.position_text);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Set Up").clicked() {
+                    let position = self.position_text.parse::<Position>().and_then(|position| {
+                        // positions are set up with the default rules
+                        position.check_rules(Rules::default())?;
+                        Ok(position)
+                    });
+                    self.status = Some(match position {
+                        Ok(position) => {
+                            self.set_game(Game::from_position(position));
+                            "Set up the position".to_string()
+                        }
+                        Err(error) => format!("Could not set up the position: {error}"),
+                    });
+                }
+                if ui.button("Current Position").clicked() {
+                    self.position_text = self.game.position().to_string();
+                }
+            });
+            if let Some(status) = &self.status {
+                ui.label(status);
+            }
+
+            ui.separator();
+            ui.checkbox(&mut self.vs_computer, "Play vs Computer");
+            ui.add_enabled_ui(self.vs_computer, |ui| {
+                egui::ComboBox::from_label("Computer plays")
+                    .selected_text(self.computer_player.to_string())
+                    .show_ui(ui, |ui| {
+                        for &player in self.game.rules().players() {
+                            ui.selectable_value(
+                                &mut self.computer_player,
+                                player,
+                                player.to_string(),
+                            );
+                        }
+                    });
+                egui::ComboBox::from_label("Engine")
+                    .selected_text(match self.computer_engine {
+                        EngineKind::AlphaBeta => "Alpha-Beta",
+                        EngineKind::Mcts => "Monte Carlo",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.add_enabled_ui(self.game.rules().num_players == 2, |ui| {
+                            ui.selectable_value(
+                                &mut self.computer_engine,
+                                EngineKind::AlphaBeta,
+                                "Alpha-Beta",
+                            );
+                        });
+                        ui.selectable_value(
+                            &mut self.computer_engine,
+                            EngineKind::Mcts,
+                            "Monte Carlo",
+                        );
+                    });
+                if self.computer_engine == EngineKind::AlphaBeta {
+                    ui.add(
+                        egui::Slider::new(&mut self.computer_think_time, 0.1..=10.0)
+                            .text("Thinking time (s)"),
+                    );
+                }
+            });
+            if self.computer_search.is_some() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Computer is thinking...");
+                });
+            }
+            ui.separator();
+            match &mut self.network {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.label("Address:");
+                        ui.text_edit_singleline(&mut self.network_address);
+                    });
+                    egui::ComboBox::from_label("Host plays")
+                        .selected_text(self.network_player.to_string())
+                        .show_ui(ui, |ui| {
+                            for &player in self.game.rules().players() {
+                                ui.selectable_value(
+                                    &mut self.network_player,
+                                    player,
+                                    player.to_string(),
+                                );
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        if ui.button("Host").clicked() {
+                            match NetSession::host(&self.network_address, self.network_player) {
+                                Ok(network) => {
+                                    self.network = Some(network);
+                                    self.status = Some(format!(
+                                        "Waiting for a player on {}",
+                                        self.network_address
+                                    ));
+                                }
+                                Err(error) => {
+                                    self.status = Some(format!("Could not host: {error}"))
+                                }
+                            }
+                        }
+                        if ui.button("Join").clicked() {
+                            match NetSession::join(&self.network_address) {
+                                Ok(network) => {
+                                    self.network = Some(network);
+                                    self.status = Some(format!(
+                                        "Joined {}, waiting for the game",
+                                        self.network_address
+                                    ));
+                                }
+                                Err(error) => {
+                                    self.status = Some(format!("Could not join: {error}"))
+                                }
+                            }
+                        }
+                    });
+                }
+                Some(network) => {
+                    let players = self
+                        .game
+                        .rules()
+                        .players()
+                        .iter()
+                        .filter(|&&player| network.plays(player))
+                        .map(State::to_string)
+                        .collect::<Vec<_>>();
+                    ui.label(match (network.is_connected(), network.is_host()) {
+                        (true, _) => format!("Playing {} over the network", players.join(", ")),
+                        (false, true) => "Waiting for a player to join".to_string(),
+                        (false, false) => "Not connected to the host".to_string(),
+                    });
+                    let mut leave = false;
+                    ui.horizontal(|ui| {
+                        if !network.is_host()
+                            && !network.is_connected()
+                            && ui.button("Reconnect").clicked()
+                        {
+                            if let Err(error) = network.reconnect() {
+                                self.status = Some(format!("Could not reconnect: {error}"));
+                            }
+                        }
+                        leave = ui.button("Leave").clicked();
+                    });
+                    if leave {
+                        self.network = None;
+                    }
+                }
+            }
+
+            ui.separator();
+            ui.checkbox(&mut self.show_heatmap, "Show engine heatmap");
+            if self.computer_engine == EngineKind::Mcts || self.show_heatmap {
+                ui.add(
+                    egui::Slider::new(&mut self.mcts.playouts, 100..=1_000_000)
+                        .logarithmic(true)
+                        .text("Playouts"),
+                );
+                let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                ui.add(egui::Slider::new(&mut self.mcts.threads, 1..=max_threads).text("Threads"));
+                ui.add(egui::DragValue::new(&mut self.mcts.seed).prefix("Seed: "));
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.game.num_moves() > 0, egui::Button::new("Undo"))
+                    .clicked()
+                {
+                    self.game.undo();
+                }
+                if ui
+                    .add_enabled(
+                        self.game.redo_moves().next().is_some(),
+                        egui::Button::new("Redo"),
+                    )
+                    .clicked()
+                {
+                    self.game.redo();
+                }
+            });
+            ui.label("History:");
+            let mut jump_to = None;
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    let num_moves = self.game.num_moves();
+                    let size = self.game.shape().size;
+                    if ui.selectable_label(num_moves == 0, "Start").clicked() {
+                        jump_to = Some(0);
+                    }
+                    for (i, (player, mv)) in self.game.moves().enumerate() {
+                        let text = format!("{}. {player} {}", i + 1, mv.display(size));
+                        if ui.selectable_label(num_moves == i + 1, text).clicked() {
+                            jump_to = Some(i + 1);
+                        }
+                    }
+                    for (i, mv) in self.game.redo_moves().enumerate() {
+                        let text = egui::RichText::new(format!(
+                            "{}. {}",
+                            num_moves + i + 1,
+                            mv.display(size)
+                        ));
+                        if ui.selectable_label(false, text.weak()).clicked() {
+                            jump_to = Some(num_moves + i + 1);
+                        }
+                    }
+                });
+            if let Some(num_moves) = jump_to {
+                self.game.jump_to(num_moves);
+            }
+
+            ui.allocate_space(ui.available_size());
+        });
+        // undoing moves can take back the end of the game
+        self.game_over = self.game.outcome().is_some();
+
+        let was_game_over = self.game_over;
+        if egui::Window::new("Game Over")
+            .open(&mut self.game_over)
+            .show(ctx, |ui| match self.game.outcome() {
+                Some(Outcome::Winner(winner)) => {
+                    ui.label(format!("{winner} won the game!"));
+                    false
+                }
+                Some(Outcome::Stalemate) => {
+                    ui.label("A stalemate has occured, nobody wins");
+                    false
+                }
+                None => true,
+            })
+            .and_then(|r| r.inner)
+            .unwrap_or(false)
+            || (was_game_over && !self.game_over)
+        {
+            self.restart();
+        }
+
+        let egui::InnerResponse {
+            inner: (rect, response),
+            response: _,
+        } = egui::CentralPanel::default()
+            .frame(egui::Frame::none())
+            .show(ctx, |ui| {
+                let size = ui.available_size();
+                let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+
+                // in physical pixels, which the shader smooths the edges over
+                let pixels_per_point = ctx.pixels_per_point();
+                self.camera.screen_size =
+                    (size.x * pixels_per_point, size.y * pixels_per_point).into();
+                self.update_per_object_data();
+
+                ui.painter().add(egui::PaintCallback {
+                    rect,
+                    callback: Arc::new(
+                        eframe::egui_wgpu::CallbackFn::new()
+                            .prepare({
+                                let camera = self.camera;
+                                let per_object_data = self.per_object_data.clone();
+                                let version = self.per_object_data_version;
+                                let sample_count = if self.msaa { 4 } else { 1 };
+                                let size = [camera.screen_size.x, camera.screen_size.y]
+                                    .map(|n| n.round() as u32);
+                                move |device, queue, encoder, resources| {
+                                    let state: &mut WindowRenderState =
+                                        resources.get_mut().unwrap();
+                                    state.prepare(
+                                        camera,
+                                        &per_object_data,
+                                        version,
+                                        sample_count,
+                                        size,
+                                        device,
+                                        queue,
+                                        encoder,
+                                    );
+                                    vec![]
+                                }
+                            })
+                            .paint(move |_info, render_pass, resources| {
+                                let state: &WindowRenderState = resources.get().unwrap();
+                                state.render(render_pass);
+                            }),
+                    ),
+                });
+
+                (rect, response)
+            });
+
+        if response.clicked() && !self.game_over && !self.is_computer_turn() {
+            let click_pos = response.interact_pointer_pos().unwrap();
+            if rect.contains(click_pos) {
+                let ndc_coords = ((click_pos - rect.left_top()) / rect.size() * 2.0
+                    - egui::Vec2::splat(1.0))
+                    * egui::vec2(1.0, -1.0);
+
+                // inverse of what is being done in vs_main inside of shader.wgsl
+                /*
+                   out.position = model.position * quad_pixels / pixels_per_unit;
+                   out.position = vec2<f32>(
+                       out.position.x * cos(-model.rotation) - out.position.y * sin(-model.rotation),
+                       out.position.y * cos(-model.rotation) + out.position.x * sin(-model.rotation),
+                   );
+                   out.position += model.object_position;
+                   out.clip_position = vec4<f32>((out.position - camera.position) * camera.scale / vec2<f32>(aspect, 1.0), 0.0, 1.0);
+                   out.clip_position = vec4<f32>(
+                       out.clip_position.x * cos(camera.rotation) - out.clip_position.y * sin(camera.rotation),
+                       out.clip_position.y * cos(camera.rotation) + out.clip_position.x * sin(camera.rotation),
+                       out.clip_position.z,
+                       out.clip_position.w,
+                   );
+                */
+
+                let unrotated_camera = cgmath::vec2(
+                    ndc_coords.x * (-self.camera.rotation).cos()
+                        - ndc_coords.y * (-self.camera.rotation).sin(),
+                    ndc_coords.y * (-self.camera.rotation).cos()
+                        + ndc_coords.x * (-self.camera.rotation).sin(),
+                );
+
+                let aspect = rect.width() / rect.height();
+
+                let position = cgmath::vec2(unrotated_camera.x * aspect, unrotated_camera.y)
+                    / self.camera.scale
+                    + self.camera.position;
+
+                let path = CellPath::from_world_position(self.game.board(), position)
+                    .filter(|_| self.is_local_turn());
+                if let Some(path) = path {
+                    if self.game.play(&path).is_ok() && self.game.outcome().is_some() {
+                        self.game_over = true;
+                    }
+                }
+            }
+        }
+
+        if response.hovered() {
+            ctx.input(|i| {
+                if i.scroll_delta.y > 0.0 {
+                    self.camera.scale *= 0.95;
+                } else if i.scroll_delta.y < 0.0 {
+                    self.camera.scale /= 0.95;
+                }
+            });
+        }
+
+        if !ctx.wants_keyboard_input() {
+            ctx.input(|i| {
+                const CAMERA_SPEED: f32 = 2.0;
+                if i.key_down(egui::Key::W) || i.key_down(egui::Key::ArrowUp) {
+                    self.camera.position.y += CAMERA_SPEED / self.camera.scale * ts;
+                }
+                if i.key_down(egui::Key::S) || i.key_down(egui::Key::ArrowDown) {
+                    self.camera.position.y -= CAMERA_SPEED / self.camera.scale * ts;
+                }
+                if i.key_down(egui::Key::A) || i.key_down(egui::Key::ArrowLeft) {
+                    self.camera.position.x -= CAMERA_SPEED / self.camera.scale * ts;
+                }
+                if i.key_down(egui::Key::D) || i.key_down(egui::Key::ArrowRight) {
+                    self.camera.position.x += CAMERA_SPEED / self.camera.scale * ts;
+                }
+            });
+        }
+    }
+}
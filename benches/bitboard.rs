@@ -0,0 +1,96 @@
+//! Compares random playouts and win checks on the [`Board`] tree with the [`BitBoard`].
+//!
+//! Run with `cargo bench --bench bitboard`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ultimate_tic_tac_toe::{BitBoard, Board, Game, Move, Rng, State};
+
+/// Runs `f` for about a second and returns the average time per run.
+fn bench(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn playout_tree(game: &mut Game, rng: &mut Rng) {
+    let mut num_moves = 0;
+    while game.outcome().is_none() {
+        let moves = game.legal_moves();
+        game.play(&moves[rng.below(moves.len())]).unwrap();
+        num_moves += 1;
+    }
+    for _ in 0..num_moves {
+        game.undo();
+    }
+}
+
+fn playout_bits(bit_board: &mut BitBoard, rng: &mut Rng) {
+    let mut history: Vec<Move> = vec![];
+    let mut player = State::Circle;
+    while bit_board.outcome().is_none() {
+        let mut moves = bit_board.legal_moves(history.last());
+        let mv = moves.swap_remove(rng.below(moves.len()));
+        bit_board.make(&mv, player);
+        history.push(mv);
+        player = player.next(2);
+    }
+    for mv in history.iter().rev() {
+        bit_board.unmake(mv);
+    }
+}
+
+/// A game with half of its moves played.
+fn midgame(num_layers: usize) -> Game {
+    let mut rng = Rng::new(1);
+    let mut game = Game::new(num_layers);
+    while game.num_moves_left() > game.num_moves() && game.outcome().is_none() {
+        let moves = game.legal_moves();
+        game.play(&moves[rng.below(moves.len())]).unwrap();
+    }
+    game
+}
+
+fn main() {
+    for num_layers in 1..=3 {
+        println!("{num_layers} layers:");
+
+        let mut game = Game::new(num_layers);
+        let mut rng = Rng::new(7);
+        let tree = bench(|| playout_tree(&mut game, &mut rng));
+        let mut bit_board = BitBoard::new(num_layers);
+        // the same seed, so both representations play the same games
+        let mut rng = Rng::new(7);
+        let bits = bench(|| playout_bits(&mut bit_board, &mut rng));
+        println!("  random playout: tree {tree:?}, bitboard {bits:?}");
+
+        let board = midgame(num_layers).board().clone();
+        let bit_board = BitBoard::try_from(&board).unwrap();
+        let tree = bench(|| {
+            black_box(black_box(&board).get_winner());
+            black_box(black_box(&board).is_stalemate());
+        });
+        let bits = bench(|| {
+            black_box(black_box(&bit_board).outcome());
+        });
+        println!("  outcome check:  tree {tree:?}, bitboard {bits:?}");
+
+        let tree = bench(|| {
+            black_box(black_box(&board).clone());
+        });
+        let bits = bench(|| {
+            black_box(black_box(&bit_board).clone());
+        });
+        println!("  copy:           tree {tree:?}, bitboard {bits:?}");
+
+        let converted = bench(|| {
+            black_box(Board::from(&BitBoard::try_from(black_box(&board)).unwrap()));
+        });
+        println!("  conversion round trip: {converted:?}");
+    }
+}
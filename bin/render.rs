@@ -0,0 +1,128 @@
+use ultimate_tic_tac_toe::{
+    board_to_svg, default_styles, Game, GameRecord, OffscreenRenderer, Position,
+};
+
+const USAGE: &str = "\
+usage: uttt-render <game> <output> [options]
+
+The game is either a file with a game record or a position like \"oxo/xox/3 o -\". Outputs
+ending in .svg are written as square vector images, all others as PNG.
+
+options:
+    --width <n>     the width of the image, default 512
+    --height <n>    the height of PNG images, default the width
+    --move <n>      draws the game after this many moves of the record, default all of them
+    --software      renders on a software adapter, which gives the same pixels everywhere
+    --msaa          smooths the edges of PNG images further with 4 samples per pixel";
+
+struct Args {
+    game: Game,
+    output: String,
+    width: u32,
+    height: u32,
+    software: bool,
+    msaa: bool,
+}
+
+fn read_game(source: &str, num_moves: Option<usize>) -> Result<Game, String> {
+    if let Ok(position) = source.parse::<Position>() {
+        return Ok(Game::from_position(position));
+    }
+    let text = std::fs::read_to_string(source)
+        .map_err(|error| format!("could not read {source}: {error}"))?;
+    let mut record: GameRecord = text.parse().map_err(|error| format!("{source}: {error}"))?;
+    if let Some(num_moves) = num_moves {
+        record.moves.truncate(num_moves);
+    }
+    record
+        .replay()
+        .map_err(|error| format!("{source}: {error}"))
+}
+
+fn parse_size(value: &str) -> Option<u32> {
+    value.parse().ok().filter(|&size| size > 0)
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut positional = vec![];
+    let mut width = 512;
+    let mut height = None;
+    let mut num_moves = None;
+    let mut software = false;
+    let mut msaa = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg.clone());
+            continue;
+        }
+        if arg == "--software" {
+            software = true;
+            continue;
+        }
+        if arg == "--msaa" {
+            msaa = true;
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+        let invalid = || format!("invalid value \"{value}\" for {arg}");
+        match arg.as_str() {
+            "--width" => width = parse_size(value).ok_or_else(invalid)?,
+            "--height" => height = Some(parse_size(value).ok_or_else(invalid)?),
+            "--move" => num_moves = Some(value.parse().map_err(|_| invalid())?),
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+
+    let [source, output] = &positional[..] else {
+        return Err("a game and an output file are needed".to_string());
+    };
+    Ok(Args {
+        game: read_game(source, num_moves)?,
+        output: output.clone(),
+        width,
+        height: height.unwrap_or(width),
+        software,
+        msaa,
+    })
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if args.output.ends_with(".svg") {
+        let svg = board_to_svg(
+            args.game.board(),
+            &args.game.target_board(),
+            &default_styles(),
+            args.width,
+        );
+        if let Err(error) = std::fs::write(&args.output, svg) {
+            eprintln!("could not write {}: {error}", args.output);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let sample_count = if args.msaa { 4 } else { 1 };
+    let image = OffscreenRenderer::new(args.software, sample_count).and_then(|mut renderer| {
+        renderer.render_board(
+            args.game.board(),
+            &args.game.target_board(),
+            &default_styles(),
+            args.width,
+            args.height,
+        )
+    });
+    if let Err(error) = image.and_then(|image| image.save_png(&args.output)) {
+        eprintln!("could not render {}: {error}", args.output);
+        std::process::exit(1);
+    }
+}
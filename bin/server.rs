@@ -0,0 +1,15 @@
+use std::io::BufRead;
+
+use ultimate_tic_tac_toe::Server;
+
+/// Plays games over stdin and stdout without the ui, see [`Server`] for the commands.
+fn main() -> std::io::Result<()> {
+    let mut server = Server::default();
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        if !server.handle(&line?, &mut stdout)? {
+            break;
+        }
+    }
+    Ok(())
+}
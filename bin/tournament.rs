@@ -0,0 +1,128 @@
+use ultimate_tic_tac_toe::{Engine, Tournament};
+
+const USAGE: &str = "\
+usage: uttt-tournament <engine> <engine> [options]
+
+Engines are written like alphabeta:nodes=20000,depth=6 or mcts:playouts=5000,threads=4.
+
+options:
+    --games <n>         the number of games, default 10
+    --layers <n,...>    the layer counts to play on in turn, default 2
+    --size <n>          the size of the boards, default 3
+    --win-length <n>    the cells in a row that win, default the size
+    --rules <rules>     the rules, like \"misere wild_draws\", default standard
+    --opening <n>       random moves at the start of every game, default 2
+    --seed <n>          the seed of the openings, default 0
+    --records <dir>     writes the record of every game to this directory";
+
+fn parse_args(args: &[String]) -> Result<(Tournament, Option<String>), String> {
+    let mut tournament = Tournament::default();
+    let mut records = None;
+    let mut engines = vec![];
+    let mut win_length = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            engines.push(arg.parse::<Engine>().map_err(|error| error.to_string())?);
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+        let invalid = || format!("invalid value \"{value}\" for {arg}");
+        match arg.as_str() {
+            "--games" => tournament.num_games = value.parse().map_err(|_| invalid())?,
+            "--layers" => {
+                tournament.layers = value
+                    .split(',')
+                    .map(|layers| layers.parse().ok().filter(|&layers| layers > 0))
+                    .collect::<Option<_>>()
+                    .ok_or_else(invalid)?
+            }
+            "--size" => tournament.shape.size = value.parse().map_err(|_| invalid())?,
+            "--win-length" => win_length = Some(value.parse().map_err(|_| invalid())?),
+            "--rules" => tournament.rules = value.parse().map_err(|_| invalid())?,
+            "--opening" => tournament.opening_moves = value.parse().map_err(|_| invalid())?,
+            "--seed" => tournament.seed = value.parse().map_err(|_| invalid())?,
+            "--records" => records = Some(value.clone()),
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+
+    tournament.engines = match engines[..] {
+        [first, second] => [first, second],
+        _ => return Err("two engines are needed".to_string()),
+    };
+    tournament.shape.win_length = win_length.unwrap_or(tournament.shape.size);
+    if !(1..=tournament.shape.size).contains(&tournament.shape.win_length) {
+        return Err("the board size or win length is invalid".to_string());
+    }
+    if let Some(&num_layers) = tournament
+        .layers
+        .iter()
+        .find(|&&num_layers| !tournament.shape.fits(num_layers))
+    {
+        return Err(format!(
+            "boards with {num_layers} layers of this size are too large"
+        ));
+    }
+    if tournament.rules.num_players != 2 {
+        return Err("tournaments are for two engines".to_string());
+    }
+    Ok((tournament, records))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (tournament, records) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if let Some(records) = &records {
+        if let Err(error) = std::fs::create_dir_all(records) {
+            eprintln!("could not create {records}: {error}");
+            std::process::exit(1);
+        }
+    }
+
+    let [first, second] = tournament.engines;
+    println!("{first} vs {second}, {} games", tournament.num_games);
+    let standings = tournament.run(|index, game| {
+        let result = match game.winner() {
+            Some(winner) => format!("{} won", tournament.engines[winner]),
+            None => "draw".to_string(),
+        };
+        let layers = game.record.tag("Layers").unwrap_or("?");
+        let circle = tournament.engines[game.first];
+        println!(
+            "game {}: {layers} layers, {circle} first, {result} after {} moves",
+            index + 1,
+            game.num_moves
+        );
+        if let Some(records) = &records {
+            let path = std::path::Path::new(records).join(format!("game-{:04}.txt", index + 1));
+            if let Err(error) = std::fs::write(&path, game.record.to_string()) {
+                eprintln!("could not write {}: {error}", path.display());
+            }
+        }
+    });
+
+    println!();
+    println!(
+        "{first}: {} wins, {} draws, {} losses",
+        standings.wins, standings.draws, standings.losses
+    );
+    let (difference, margin) = standings.elo_difference();
+    println!("Elo difference: {difference:+.1} +/- {margin:.1}");
+    println!(
+        "Average game length: {:.1} moves",
+        standings.average_length()
+    );
+    for (engine, name) in tournament.engines.iter().enumerate() {
+        println!(
+            "Time per move of {name}: {:.1} ms",
+            standings.time_per_move(engine).as_secs_f64() * 1000.0
+        );
+    }
+}
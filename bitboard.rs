@@ -0,0 +1,399 @@
+use crate::{Board, BoardShape, CellPath, Element, Move, Outcome, Rules, State};
+
+/// All cells of a board, cell (x, y) is bit `x * 3 + y`.
+const FULL: u16 = 0b111_111_111;
+
+const WIN_LINES: [u16; 8] = [
+    0b000_000_111,
+    0b000_111_000,
+    0b111_000_000,
+    0b001_001_001,
+    0b010_010_010,
+    0b100_100_100,
+    0b100_010_001,
+    0b001_010_100,
+];
+
+/// Whether the cells in a mask contain a line, for every possible mask.
+static WINS: [bool; 512] = {
+    let mut wins = [false; 512];
+    let mut mask = 0;
+    while mask < wins.len() {
+        let mut i = 0;
+        while i < WIN_LINES.len() {
+            let line = WIN_LINES[i] as usize;
+            if mask & line == line {
+                wins[mask] = true;
+            }
+            i += 1;
+        }
+        mask += 1;
+    }
+    wins
+};
+
+fn cell_index((x, y): (usize, usize)) -> usize {
+    x * 3 + y
+}
+
+/// One board as masks of the cells that each player has, where the cells of boards above the
+/// last layer are the sub-boards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Masks {
+    circle: u16,
+    cross: u16,
+    /// Sub-boards that are full without a winner.
+    drawn: u16,
+}
+
+impl Masks {
+    fn winner(&self) -> Option<State> {
+        if WINS[self.circle as usize] {
+            Some(State::Circle)
+        } else if WINS[self.cross as usize] {
+            Some(State::Cross)
+        } else {
+            None
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.circle | self.cross | self.drawn == FULL
+    }
+
+    fn is_decided(&self) -> bool {
+        self.winner().is_some() || self.is_full()
+    }
+
+    fn set(&mut self, bit: u16, state: Option<State>) {
+        match state {
+            Some(State::Circle) => self.circle |= bit,
+            Some(State::Cross) => self.cross |= bit,
+            Some(State::Triangle | State::Square) => panic!("bit boards only have two players"),
+            None => {}
+        }
+    }
+
+    fn get(&self, bit: u16) -> Option<State> {
+        if self.circle & bit != 0 {
+            Some(State::Circle)
+        } else if self.cross & bit != 0 {
+            Some(State::Cross)
+        } else {
+            None
+        }
+    }
+
+    /// Marks the cell of a sub-board with these masks in its parent.
+    fn mark_in(&self, parent: &mut Masks, bit: u16) {
+        parent.circle &= !bit;
+        parent.cross &= !bit;
+        parent.drawn &= !bit;
+        match self.winner() {
+            Some(winner) => parent.set(bit, Some(winner)),
+            None if self.is_full() => parent.drawn |= bit,
+            None => {}
+        }
+    }
+}
+
+/// A [`Board`] stored as bit masks for searching, where every move and every win check only
+/// touches one board per layer.
+///
+/// The boards of all layers are kept in one flat list, the root first and then layer by layer.
+/// The sub-board in cell `c` of the `i`th board of a layer is the `i * 9 + c`th board of the next
+/// layer. Won sub-boards are marked in their parents like played cells, so the winner of any
+/// board is a single table lookup. Only 3 by 3 boards with 3 in a row to win and the standard
+/// [`Rules`] fit in the masks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitBoard {
+    num_layers: usize,
+    boards: Vec<Masks>,
+}
+
+/// The index of the first board of `layer` in the flat list of boards.
+fn layer_offset(layer: usize) -> usize {
+    (9usize.pow(layer as u32) - 1) / 8
+}
+
+impl BitBoard {
+    pub fn new(num_layers: usize) -> Self {
+        assert!(num_layers > 0);
+        Self {
+            num_layers,
+            boards: vec![Masks::default(); layer_offset(num_layers)],
+        }
+    }
+
+    pub fn num_layers(&self) -> usize {
+        self.num_layers
+    }
+
+    /// The index of the board that contains the element at `path` and its bit in that board.
+    fn locate(&self, path: &CellPath) -> (usize, u16) {
+        let (&last, parents) = path.split_last().expect("the root has no parent board");
+        let index = parents
+            .iter()
+            .fold(0, |index, &cell| index * 9 + cell_index(cell));
+        (layer_offset(parents.len()) + index, 1 << cell_index(last))
+    }
+
+    /// The player that has the cell at `path`, or that won the sub-board at `path`.
+    pub fn get_state(&self, path: &CellPath) -> Option<State> {
+        let (board, bit) = self.locate(path);
+        self.boards[board].get(bit)
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        let root = &self.boards[0];
+        match root.winner() {
+            Some(winner) => Some(Outcome::Winner(winner)),
+            None if root.is_full() => Some(Outcome::Stalemate),
+            None => None,
+        }
+    }
+
+    /// Plays `player` in the empty cell at `mv` and marks the boards that this decides.
+    pub fn make(&mut self, mv: &Move, player: State) {
+        debug_assert_eq!(mv.len(), self.num_layers);
+        let (board, bit) = self.locate(mv);
+        self.boards[board].set(bit, Some(player));
+        self.update_parents(mv.len() - 1, board - layer_offset(mv.len() - 1));
+    }
+
+    /// Takes back the move at `mv` and the boards that it decided.
+    pub fn unmake(&mut self, mv: &Move) {
+        let (board, bit) = self.locate(mv);
+        let masks = &mut self.boards[board];
+        masks.circle &= !bit;
+        masks.cross &= !bit;
+        self.update_parents(mv.len() - 1, board - layer_offset(mv.len() - 1));
+    }
+
+    /// Marks the `index`th board of `layer` in its parent, and so on up to the root, stopping
+    /// once a parent does not change.
+    fn update_parents(&mut self, mut layer: usize, mut index: usize) {
+        while layer > 0 {
+            let child = self.boards[layer_offset(layer) + index];
+            let parent = &mut self.boards[layer_offset(layer - 1) + index / 9];
+            let old_parent = *parent;
+            child.mark_in(parent, 1 << (index % 9));
+            if *parent == old_parent {
+                break;
+            }
+            layer -= 1;
+            index /= 9;
+        }
+    }
+
+    /// Whether the board at `path` still has cells that can be played in, like
+    /// [`is_playable`](crate::is_playable).
+    pub fn is_playable(&self, path: &CellPath) -> bool {
+        if path.len() >= self.num_layers {
+            return false;
+        }
+        let mut index = 0;
+        for (layer, &cell) in path.iter().enumerate() {
+            let masks = &self.boards[layer_offset(layer) + index];
+            if masks.get(1 << cell_index(cell)).is_some() {
+                return false;
+            }
+            index = index * 9 + cell_index(cell);
+        }
+        !self.boards[layer_offset(path.len()) + index].is_decided()
+    }
+
+    /// The board the next move has to be played in, like
+    /// [`get_target_board`](crate::get_target_board).
+    pub fn target_board(&self, last_move: Option<&Move>) -> CellPath {
+        let mut target: CellPath = match last_move.map(|path| &path[..]) {
+            Some([_, rest @ ..]) => rest.iter().copied().collect(),
+            _ => CellPath::new(),
+        };
+        while !target.is_empty() && !self.is_playable(&target) {
+            target.pop();
+        }
+        target
+    }
+
+    /// The moves that can be played after `last_move`, none once the game is over.
+    pub fn legal_moves(&self, last_move: Option<&Move>) -> Vec<Move> {
+        let mut moves = vec![];
+        if self.outcome().is_some() {
+            return moves;
+        }
+
+        let mut path = self.target_board(last_move);
+        let index = path
+            .iter()
+            .fold(0, |index, &cell| index * 9 + cell_index(cell));
+        self.collect_moves(path.len(), index, &mut path, &mut moves);
+        moves
+    }
+
+    fn collect_moves(&self, layer: usize, index: usize, path: &mut Move, moves: &mut Vec<Move>) {
+        let masks = &self.boards[layer_offset(layer) + index];
+        let taken = masks.circle | masks.cross | masks.drawn;
+        for cell in 0..9 {
+            if taken & (1 << cell) != 0 {
+                continue;
+            }
+            path.push((cell / 3, cell % 3));
+            if layer + 1 == self.num_layers {
+                moves.push(path.clone());
+            } else {
+                self.collect_moves(layer + 1, index * 9 + cell, path, moves);
+            }
+            path.pop();
+        }
+    }
+
+    fn fill(&mut self, board: &Board, layer: usize, index: usize) {
+        for (x, column) in board.elements().iter().enumerate() {
+            for (y, element) in column.iter().enumerate() {
+                let cell = cell_index((x, y));
+                match element {
+                    &Element::State(state) => {
+                        self.boards[layer_offset(layer) + index].set(1 << cell, state)
+                    }
+                    Element::Board(sub_board) | Element::Won(_, sub_board) => {
+                        let child = index * 9 + cell;
+                        self.fill(sub_board, layer + 1, child);
+                        let child = self.boards[layer_offset(layer + 1) + child];
+                        child.mark_in(&mut self.boards[layer_offset(layer) + index], 1 << cell);
+                    }
+                }
+            }
+        }
+    }
+
+    fn build(&self, layer: usize, index: usize) -> Board {
+        let masks = &self.boards[layer_offset(layer) + index];
+        let mut board = Board::default();
+        for x in 0..3 {
+            for y in 0..3 {
+                let cell = cell_index((x, y));
+                let state = masks.get(1 << cell);
+                let element = if layer + 1 == self.num_layers {
+                    Element::State(state)
+                } else {
+                    let sub_board = Box::new(self.build(layer + 1, index * 9 + cell));
+                    match state {
+                        Some(winner) => Element::Won(winner, sub_board),
+                        None => Element::Board(sub_board),
+                    }
+                };
+                board.set(&[(x, y)], element);
+            }
+        }
+        board
+    }
+}
+
+/// Why a [`Board`] can't be turned into a [`BitBoard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitBoardError {
+    /// Bit boards are always 3 by 3 with 3 in a row to win.
+    UnsupportedShape(BoardShape),
+    /// Bit boards only know the standard rules for two players.
+    UnsupportedRules,
+    /// Some parts of the board have more layers than others.
+    UnevenLayers,
+}
+
+impl std::fmt::Display for BitBoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitBoardError::UnsupportedShape(shape) => write!(
+                f,
+                "bit boards are 3 by 3, not {} by {} with {} in a row",
+                shape.size, shape.size, shape.win_length
+            ),
+            BitBoardError::UnsupportedRules => {
+                write!(f, "bit boards only know the standard rules")
+            }
+            BitBoardError::UnevenLayers => write!(
+                f,
+                "the board does not have the same number of layers everywhere"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BitBoardError {}
+
+/// The conversion is lossless for boards where exactly the sub-boards that have a winner are
+/// [`Element::Won`], which is how [`Game`](crate::Game) keeps them.
+impl TryFrom<&Board> for BitBoard {
+    type Error = BitBoardError;
+
+    fn try_from(board: &Board) -> Result<Self, Self::Error> {
+        if board.shape() != BoardShape::default() {
+            return Err(BitBoardError::UnsupportedShape(board.shape()));
+        }
+        if board.rules() != Rules::default() {
+            return Err(BitBoardError::UnsupportedRules);
+        }
+        let num_layers = board.num_layers().ok_or(BitBoardError::UnevenLayers)?;
+        let mut bit_board = Self::new(num_layers);
+        bit_board.fill(board, 0, 0);
+        Ok(bit_board)
+    }
+}
+
+impl From<&BitBoard> for Board {
+    fn from(bit_board: &BitBoard) -> Self {
+        bit_board.build(0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcts::Rng, Game};
+
+    #[test]
+    fn win_table() {
+        for line in WIN_LINES {
+            assert!(WINS[line as usize]);
+        }
+        assert!(WINS[(FULL & !0b000_010_000) as usize]);
+        assert!(!WINS[0b011_101_110]);
+        assert_eq!(WINS.iter().filter(|&&win| win).count(), 282);
+    }
+
+    #[test]
+    fn matches_the_board_tree() {
+        let mut rng = Rng::new(3);
+        for num_layers in 1..=3 {
+            let mut game = Game::new(num_layers);
+            let mut bit_board = BitBoard::new(num_layers);
+            while game.outcome().is_none() {
+                let moves = bit_board.legal_moves(game.last_move());
+                assert_eq!(moves, game.legal_moves());
+                let mv = &moves[rng.below(moves.len())];
+                bit_board.make(mv, game.current_player());
+                game.play(mv).unwrap();
+                assert_eq!(bit_board.outcome(), game.outcome());
+                assert_eq!(Board::from(&bit_board), *game.board());
+                assert_eq!(BitBoard::try_from(game.board()), Ok(bit_board.clone()));
+            }
+            for (_, mv) in game.moves().collect::<Vec<_>>().into_iter().rev() {
+                bit_board.unmake(mv);
+            }
+            assert_eq!(bit_board, BitBoard::new(num_layers));
+        }
+
+        let game = Game::with_shape(
+            1,
+            BoardShape {
+                size: 4,
+                win_length: 3,
+            },
+        );
+        assert!(matches!(
+            BitBoard::try_from(game.board()),
+            Err(BitBoardError::UnsupportedShape(_))
+        ));
+    }
+}
@@ -0,0 +1,28 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+@group(0)
+@binding(0)
+var board_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var board_sampler: sampler;
+
+// one triangle that covers the whole viewport
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u)) * 2.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    out.tex_coord = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(board_texture, board_sampler, in.tex_coord);
+}
@@ -1,7 +1,28 @@
+use crate::Rules;
+
+/// A player, named after the symbol they play with by default. Circle and Cross always play,
+/// Triangle and Square join in games with more players, in this order.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     Circle,
     Cross,
+    Triangle,
+    Square,
+}
+
+impl State {
+    /// Every player in the order they take turns.
+    pub const ALL: [State; 4] = [State::Circle, State::Cross, State::Triangle, State::Square];
+
+    /// The player whose turn comes after this one in a game of `num_players`.
+    pub fn next(self, num_players: usize) -> State {
+        Self::ALL[(self as usize + 1) % num_players]
+    }
+
+    /// The player whose turn came before this one in a game of `num_players`.
+    pub fn previous(self, num_players: usize) -> State {
+        Self::ALL[(self as usize + num_players - 1) % num_players]
+    }
 }
 
 impl std::fmt::Display for State {
@@ -9,14 +30,142 @@ impl std::fmt::Display for State {
         match self {
             State::Circle => write!(f, "Circle"),
             State::Cross => write!(f, "Cross"),
+            State::Triangle => write!(f, "Triangle"),
+            State::Square => write!(f, "Square"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseStateError(String);
+
+impl std::fmt::Display for ParseStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown player `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseStateError {}
+
+impl std::str::FromStr for State {
+    type Err = ParseStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Circle" => Ok(State::Circle),
+            "Cross" => Ok(State::Cross),
+            "Triangle" => Ok(State::Triangle),
+            "Square" => Ok(State::Square),
+            _ => Err(ParseStateError(s.to_string())),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// The number of cells along each side of a board and how many of them in a row win it, which
+/// is the same for every layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoardShape {
+    pub size: usize,
+    pub win_length: usize,
+}
+
+impl Default for BoardShape {
+    fn default() -> Self {
+        Self {
+            size: 3,
+            win_length: 3,
+        }
+    }
+}
+
+/// The directions lines go in: up, right and both diagonals.
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+impl BoardShape {
+    /// The most layers a game can have.
+    pub const MAX_LAYERS: usize = 16;
+    /// The most cells a game can have, as many as 6 layers of 3 by 3 boards.
+    pub const MAX_CELLS: usize = 531_441;
+
+    /// Whether a game of `num_layers` layers of boards of this shape stays within
+    /// [`MAX_LAYERS`](Self::MAX_LAYERS) and [`MAX_CELLS`](Self::MAX_CELLS). Games from files or
+    /// other players are checked with it, so they can't take all of the memory.
+    pub fn fits(self, num_layers: usize) -> bool {
+        let board_cells = self.size.saturating_mul(self.size);
+        num_layers <= Self::MAX_LAYERS
+            && (0..num_layers)
+                .try_fold(1usize, |cells, _| {
+                    cells
+                        .checked_mul(board_cells)
+                        .filter(|&cells| cells <= Self::MAX_CELLS)
+                })
+                .is_some()
+    }
+
+    /// Every row, column and diagonal of `win_length` cells on the board.
+    pub fn lines(self) -> impl Iterator<Item = Line> {
+        DIRECTIONS.into_iter().flat_map(move |direction| {
+            (0..self.size)
+                .flat_map(move |x| (0..self.size).filter_map(move |y| self.line((x, y), direction)))
+        })
+    }
+
+    fn line(self, start: (usize, usize), direction: (isize, isize)) -> Option<Line> {
+        let line = Line {
+            start,
+            direction,
+            length: self.win_length,
+        };
+        line.cells()
+            .all(|(x, y)| x < self.size && y < self.size)
+            .then_some(line)
+    }
+
+    /// The index of every line through `cell` in [`Board`]'s line counts.
+    fn lines_through(self, (x, y): (usize, usize)) -> impl Iterator<Item = usize> {
+        DIRECTIONS
+            .into_iter()
+            .enumerate()
+            .flat_map(move |(i, direction)| {
+                (0..self.win_length as isize).filter_map(move |offset| {
+                    let start = (
+                        x.checked_add_signed(-offset * direction.0)?,
+                        y.checked_add_signed(-offset * direction.1)?,
+                    );
+                    self.line(start, direction)?;
+                    Some((i * self.size + start.0) * self.size + start.1)
+                })
+            })
+    }
+}
+
+/// A row, column or diagonal of cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+    pub start: (usize, usize),
+    pub direction: (isize, isize),
+    pub length: usize,
+}
+
+impl Line {
+    /// The cells of the line, which wrap around to huge numbers when it leaves the board to the
+    /// left or the bottom.
+    pub fn cells(self) -> impl Iterator<Item = (usize, usize)> {
+        (0..self.length as isize).map(move |i| {
+            (
+                self.start.0.wrapping_add_signed(i * self.direction.0),
+                self.start.1.wrapping_add_signed(i * self.direction.1),
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Element {
     State(Option<State>),
     Board(Box<Board>),
+    /// A board that has been won, it is kept around so its moves can still be shown.
+    Won(State, Box<Board>),
 }
 
 impl Element {
@@ -24,6 +173,37 @@ impl Element {
         match self {
             &Element::State(state) => state,
             Element::Board(board) => board.get_winner(),
+            &Element::Won(winner, _) => Some(winner),
+        }
+    }
+
+    /// Whether the element has been played, won or drawn, so it does not change anymore as far as
+    /// its board is concerned.
+    pub fn is_decided(&self) -> bool {
+        match self {
+            Element::State(state) => state.is_some(),
+            Element::Board(board) => board.get_winner().is_some() || board.is_stalemate(),
+            Element::Won(_, _) => true,
+        }
+    }
+
+    /// Whether `player` could still get this element, if every empty cell below it went their way.
+    pub fn can_win(&self, player: State) -> bool {
+        match self {
+            &Element::State(state) => state.unwrap_or(player) == player,
+            Element::Board(board) => board.can_win(player),
+            &Element::Won(winner, _) => winner == player,
+        }
+    }
+
+    /// The number of empty cells that can still be played in, which are not on a won board
+    /// unless the [`Rules`] allow playing there.
+    pub fn num_moves_left(&self) -> usize {
+        match self {
+            Element::State(state) => usize::from(state.is_none()),
+            Element::Board(board) => board.num_moves_left(),
+            Element::Won(_, board) if board.rules().play_in_won_boards => board.num_moves_left(),
+            Element::Won(_, _) => 0,
         }
     }
 }
@@ -34,164 +214,567 @@ impl Default for Element {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+/// A board of elements, which keeps its outcome up to date as they change.
+///
+/// Elements can only be changed with [`set`](Board::set), which updates the cached state of the
+/// boards along the way, so the outcome and the number of moves left never walk the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Board {
-    pub elements: [[Element; 3]; 3],
+    shape: BoardShape,
+    rules: Rules,
+    /// Indexed by x, then y.
+    elements: Vec<Vec<Element>>,
+    winner: Option<State>,
+    num_decided: usize,
+    num_moves_left: usize,
+    /// How many elements of each line every player has, indexed by the line's direction and
+    /// start cell. Starts that have no line in a direction are always 0.
+    line_counts: Vec<[usize; 4]>,
+    /// Whether each player can still win, by a line that has not been blocked or by the majority
+    /// tiebreak.
+    winnable: [bool; 4],
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(BoardShape::default())
+    }
 }
 
 impl Board {
-    pub fn is_stalemate(&self) -> bool {
-        self.elements.iter().flatten().all(|state| match state {
-            Element::State(state) => state.is_some(),
-            Element::Board(board) => board.is_stalemate() || board.get_winner().is_some(),
-        }) && self.get_winner().is_none()
+    /// An empty board of cells with the standard rules.
+    pub fn new(shape: BoardShape) -> Self {
+        Self::with_rules(shape, Rules::default())
     }
 
-    pub fn get_winner(&self) -> Option<State> {
-        let states: [[Option<State>; 3]; 3] =
-            std::array::from_fn(|x| std::array::from_fn(|y| self.elements[x][y].get_state()));
-
-        fn check_winner(state: State, states: &[[Option<State>; 3]; 3]) -> bool {
-            // Check vertical
-            for column in states {
-                if column.iter().all(|&s| s == Some(state)) {
-                    return true;
-                }
-            }
+    /// An empty board of cells.
+    pub fn with_rules(shape: BoardShape, rules: Rules) -> Self {
+        let open = shape.lines().next().is_some();
+        Self {
+            shape,
+            rules,
+            elements: vec![vec![Element::default(); shape.size]; shape.size],
+            winner: None,
+            num_decided: 0,
+            num_moves_left: shape.size * shape.size,
+            line_counts: vec![[0; 4]; DIRECTIONS.len() * shape.size * shape.size],
+            winnable: State::ALL.map(|player| open && rules.players().contains(&player)),
+        }
+    }
+
+    pub fn shape(&self) -> BoardShape {
+        self.shape
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
 
-            // Check horizontal
-            for y in 0..3 {
-                let mut won = true;
-                #[allow(clippy::needless_range_loop)]
-                for x in 0..3 {
-                    if states[x][y] != Some(state) {
-                        won = false;
-                        break;
+    /// Switches this board and all of its sub-boards to `rules`, which can change who won them.
+    pub fn set_rules(&mut self, rules: Rules) {
+        let elements = std::mem::take(&mut self.elements);
+        *self = Self::with_rules(self.shape, rules);
+        for (x, column) in elements.into_iter().enumerate() {
+            for (y, element) in column.into_iter().enumerate() {
+                let element = match element {
+                    Element::State(_) => element,
+                    Element::Board(mut board) | Element::Won(_, mut board) => {
+                        board.set_rules(rules);
+                        match board.get_winner() {
+                            Some(winner) => Element::Won(winner, board),
+                            None => Element::Board(board),
+                        }
                     }
-                }
-                if won {
-                    return true;
-                }
+                };
+                self.set(&[(x, y)], element);
             }
+        }
+    }
 
-            // Check diagonal right
-            {
-                let mut won = true;
-                #[allow(clippy::needless_range_loop)]
-                for i in 0..3 {
-                    if states[i][i] != Some(state) {
-                        won = false;
-                        break;
-                    }
-                }
-                if won {
-                    return true;
-                }
+    pub fn elements(&self) -> &[Vec<Element>] {
+        &self.elements
+    }
+
+    /// Replaces the element at `path` and returns the old one.
+    ///
+    /// Panics if `path` is empty, goes through a cell or leaves the board.
+    pub fn set(&mut self, path: &[(usize, usize)], element: Element) -> Element {
+        let (&(x, y), rest) = path.split_first().expect("the path names an element");
+        // whoever played, if both players get a line at once
+        let player = element.get_state();
+        let old_owners = self.line_owners(&self.elements[x][y]);
+        let old_decided = self.elements[x][y].is_decided();
+        let old_moves_left = self.elements[x][y].num_moves_left();
+
+        let old = if rest.is_empty() {
+            std::mem::replace(&mut self.elements[x][y], element)
+        } else {
+            match &mut self.elements[x][y] {
+                Element::Board(board) | Element::Won(_, board) => board.set(rest, element),
+                Element::State(_) => panic!("the path goes through a cell"),
             }
+        };
 
-            // Check diagonal left
-            {
-                let mut won = true;
-                for i in 0..3 {
-                    if states[2 - i][i] != Some(state) {
-                        won = false;
-                        break;
-                    }
-                }
-                if won {
-                    return true;
+        let element = &self.elements[x][y];
+        let owners = self.line_owners(element);
+        if owners != old_owners {
+            for line in self.shape.lines_through((x, y)) {
+                let counts = &mut self.line_counts[line];
+                for (count, (owner, old_owner)) in
+                    counts.iter_mut().zip(owners.iter().zip(old_owners))
+                {
+                    *count = *count + usize::from(*owner) - usize::from(old_owner);
                 }
             }
+        }
+        self.num_decided =
+            self.num_decided + usize::from(element.is_decided()) - usize::from(old_decided);
+        self.num_moves_left = self.num_moves_left + element.num_moves_left() - old_moves_left;
+        self.update_winner(player);
+        self.winnable = State::ALL.map(|player| {
+            if !self.rules.players().contains(&player) {
+                return false;
+            }
+            let line_player = if self.rules.misere {
+                player.previous(self.rules.num_players)
+            } else {
+                player
+            };
+            self.shape.lines().any(|line| {
+                line.cells().all(|(x, y)| {
+                    let element = &self.elements[x][y];
+                    element.can_win(line_player) || self.line_owners(element)[line_player as usize]
+                })
+            }) || self.can_win_majority(player)
+        });
+        old
+    }
 
-            false
+    /// The players that an element counts for in lines.
+    fn line_owners(&self, element: &Element) -> [bool; 4] {
+        match element.get_state() {
+            Some(state) => State::ALL.map(|player| player == state),
+            None if self.rules.wild_draws && element.is_decided() => [true; 4],
+            None => [false; 4],
         }
+    }
 
-        if check_winner(State::Circle, &states) {
-            Some(State::Circle)
-        } else if check_winner(State::Cross, &states) {
-            Some(State::Cross)
-        } else {
-            None
+    /// Finds the winner after the element that `player` played in changed. The player that got
+    /// the first line keeps the board as long as that line is there, even if another player gets
+    /// one later on a board that is still played in. In misère the board goes to the player after
+    /// the one that got the line.
+    fn update_winner(&mut self, player: Option<State>) {
+        let win_length = self.shape.win_length;
+        let has_line = |player: State| {
+            self.line_counts
+                .iter()
+                .any(|counts| counts[player as usize] == win_length)
+        };
+        let num_players = self.rules.num_players;
+        let misere = self.rules.misere;
+        let line_player = self
+            .winner
+            .map(|winner| {
+                if misere {
+                    winner.previous(num_players)
+                } else {
+                    winner
+                }
+            })
+            .into_iter()
+            .chain(player)
+            .chain(State::ALL)
+            .find(|&player| has_line(player));
+        self.winner = match line_player {
+            Some(line_player) if misere => Some(line_player.next(num_players)),
+            Some(line_player) => Some(line_player),
+            None => self.majority_winner(),
+        };
+    }
+
+    fn has_sub_boards(&self) -> bool {
+        !matches!(self.elements[0][0], Element::State(_))
+    }
+
+    /// With the majority tiebreak, the player that has more elements of a full board of
+    /// sub-boards than anyone else.
+    fn majority_winner(&self) -> Option<State> {
+        if !self.rules.majority_tiebreak
+            || !self.has_sub_boards()
+            || self.num_decided < self.shape.size * self.shape.size
+        {
+            return None;
+        }
+        let counts = self.state_counts();
+        let most = counts.iter().max().copied();
+        let mut leaders = State::ALL
+            .into_iter()
+            .filter(|&player| Some(counts[player as usize]) == most);
+        match (leaders.next(), leaders.next()) {
+            (Some(leader), None) => Some(leader),
+            _ => None,
+        }
+    }
+
+    /// How many elements each player has.
+    fn state_counts(&self) -> [usize; 4] {
+        let mut counts = [0; 4];
+        for state in self
+            .elements
+            .iter()
+            .flatten()
+            .filter_map(Element::get_state)
+        {
+            counts[state as usize] += 1;
+        }
+        counts
+    }
+
+    /// Whether `player` could still win with the majority tiebreak, if they got every element
+    /// that is still open.
+    fn can_win_majority(&self, player: State) -> bool {
+        if !self.rules.majority_tiebreak || !self.has_sub_boards() {
+            return false;
         }
+        let most = self
+            .elements
+            .iter()
+            .flatten()
+            .filter(|element| element.can_win(player))
+            .count();
+        let counts = self.state_counts();
+        State::ALL
+            .into_iter()
+            .filter(|&other| other != player)
+            .all(|other| most > counts[other as usize])
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        self.num_decided == self.shape.size * self.shape.size && self.winner.is_none()
+    }
+
+    pub fn get_winner(&self) -> Option<State> {
+        self.winner
+    }
+
+    /// Whether `player` could still win this board, if every empty cell on it went their way.
+    pub fn can_win(&self, player: State) -> bool {
+        match self.winner {
+            Some(winner) => winner == player,
+            None => self.winnable[player as usize],
+        }
+    }
+
+    /// Whether the board can only end in a stalemate, because no player can win it anymore, even
+    /// though there may be empty cells left.
+    pub fn is_dead(&self) -> bool {
+        !self
+            .rules
+            .players()
+            .iter()
+            .any(|&player| self.can_win(player))
+    }
+
+    /// The number of empty cells that can still be played in, see [`Element::num_moves_left`].
+    pub fn num_moves_left(&self) -> usize {
+        self.num_moves_left
+    }
+
+    /// The number of nested boards, or `None` if it differs between the elements.
+    pub fn num_layers(&self) -> Option<usize> {
+        let mut layers = self.elements.iter().flatten().map(|element| match element {
+            Element::State(_) => Some(1),
+            Element::Board(board) | Element::Won(_, board) => Some(board.num_layers()? + 1),
+        });
+        let first = layers.next()??;
+        layers.all(|layers| layers == Some(first)).then_some(first)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{mcts::Rng, Game};
 
     #[test]
     fn nothing() {
-        let board = Board {
-            elements: [
-                [
-                    Element::State(None),
-                    Element::State(None),
-                    Element::State(None),
-                ],
-                [
-                    Element::State(None),
-                    Element::State(None),
-                    Element::State(None),
-                ],
-                [
-                    Element::State(None),
-                    Element::State(None),
-                    Element::State(None),
-                ],
-            ],
-        };
+        let board: Board = "3/3/3".parse().unwrap();
         assert!(board.get_winner().is_none());
         assert!(!board.is_stalemate());
     }
 
     #[test]
     fn cross_horizontal_win() {
-        let board = Board {
-            elements: [
-                [
-                    Element::State(Some(State::Cross)),
-                    Element::State(None),
-                    Element::State(None),
-                ],
-                [
-                    Element::State(Some(State::Cross)),
-                    Element::State(None),
-                    Element::State(None),
-                ],
-                [
-                    Element::State(Some(State::Cross)),
-                    Element::State(None),
-                    Element::State(None),
-                ],
-            ],
-        };
+        let board: Board = "3/3/xxx".parse().unwrap();
         assert_eq!(board.get_winner(), Some(State::Cross));
         assert!(!board.is_stalemate());
     }
 
     #[test]
     fn circle_diagonal_win() {
-        let board = Board {
-            elements: [
-                [
-                    Element::State(Some(State::Cross)),
-                    Element::State(None),
-                    Element::State(None),
-                ],
-                [
-                    Element::State(None),
-                    Element::State(Some(State::Cross)),
-                    Element::State(None),
-                ],
-                [
-                    Element::State(None),
-                    Element::State(None),
-                    Element::State(Some(State::Cross)),
-                ],
-            ],
+        let board: Board = "2x/1x1/x2".parse().unwrap();
+        assert_eq!(board.get_winner(), Some(State::Cross));
+        assert!(!board.is_stalemate());
+    }
+
+    #[test]
+    fn four_in_a_row() {
+        let shape = BoardShape {
+            size: 5,
+            win_length: 4,
         };
+        assert_eq!(shape.lines().count(), 28);
+
+        let mut board = Board::new(shape);
+        for cell in [(1, 1), (2, 2), (3, 3)] {
+            board.set(&[cell], Element::State(Some(State::Circle)));
+        }
+        assert_eq!(board.get_winner(), None);
+        board.set(&[(4, 4)], Element::State(Some(State::Circle)));
+        assert_eq!(board.get_winner(), Some(State::Circle));
+        board.set(&[(4, 4)], Element::State(None));
+        board.set(&[(0, 0)], Element::State(Some(State::Circle)));
+        assert_eq!(board.get_winner(), Some(State::Circle));
+        assert_eq!(board.num_moves_left(), 21);
+    }
+
+    #[test]
+    fn dead_positions() {
+        let board: Board = "xo1/oxx/oxo".parse().unwrap();
+        assert!(board.is_dead());
+        assert!(!board.is_stalemate());
+
+        let drawn = "xoo/oxx/oxo";
+        let empty = "3/3/3";
+        let board: Board = format!(
+            "({empty})({empty})({drawn})/({empty})({drawn})({empty})/({drawn})({empty})({empty})"
+        )
+        .parse()
+        .unwrap();
+        assert!(board.is_dead());
+        assert_eq!(board.num_moves_left(), 54);
+
+        let board: Board = format!(
+            "({empty})({empty})({drawn})/({empty})({empty})({empty})/({drawn})({empty})({empty})"
+        )
+        .parse()
+        .unwrap();
+        assert!(!board.is_dead());
+        assert!(board.can_win(State::Circle) && board.can_win(State::Cross));
+    }
+
+    #[test]
+    fn cache_follows_changes() {
+        let mut board: Board =
+            "(3/3/3)(3/3/3)(3/3/3)/(xox/oxo/ox1)(3/3/3)(3/3/3)/(3/3/3)(3/3/3)(3/3/3)"
+                .parse()
+                .unwrap();
+        assert_eq!(board.num_moves_left(), 73);
+        assert!(!board.elements()[0][1].is_decided());
+
+        board.set(&[(0, 1), (2, 0)], Element::State(Some(State::Circle)));
+        assert!(board.elements()[0][1].is_decided());
+        assert_eq!(board.elements()[0][1].get_state(), None);
+        assert_eq!(board.num_moves_left(), 72);
+
+        let old = board.set(&[(0, 1), (2, 0)], Element::State(Some(State::Cross)));
+        assert_eq!(old, Element::State(Some(State::Circle)));
+        assert_eq!(board.elements()[0][1].get_state(), Some(State::Cross));
+
+        board.set(&[(0, 1), (2, 0)], Element::State(None));
+        assert_eq!(board.elements()[0][1].get_state(), None);
+        assert!(!board.elements()[0][1].is_decided());
+        assert_eq!(board.num_moves_left(), 73);
+    }
+
+    fn sub_boards(rows: [[&str; 3]; 3]) -> Board {
+        rows.map(|row| row.map(|sub_board| format!("({sub_board})")).concat())
+            .join("/")
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn misere() {
+        let mut board: Board = "3/3/xx1".parse().unwrap();
+        board.set_rules(Rules {
+            misere: true,
+            ..Default::default()
+        });
+        assert!(board.can_win(State::Circle) && board.can_win(State::Cross));
+        board.set(&[(2, 0)], Element::State(Some(State::Cross)));
+        assert_eq!(board.get_winner(), Some(State::Circle));
+    }
+
+    #[test]
+    fn play_in_won_boards() {
+        let empty = "3/3/3";
+        let mut board = sub_boards([
+            ["3/3/xxx", empty, empty],
+            [empty, empty, empty],
+            [empty, empty, empty],
+        ]);
+        assert_eq!(board.num_moves_left(), 72);
+        assert!(!crate::is_playable(&board, &"1".parse().unwrap()));
+
+        board.set_rules(Rules {
+            play_in_won_boards: true,
+            ..Default::default()
+        });
+        assert_eq!(board.num_moves_left(), 78);
+        assert!(crate::is_playable(&board, &"1".parse().unwrap()));
+        for x in 0..3 {
+            board.set(&[(0, 2), (x, 2)], Element::State(Some(State::Circle)));
+        }
+        // the first line keeps the board
+        assert_eq!(board.elements()[0][2].get_state(), Some(State::Cross));
+        assert_eq!(board.num_moves_left(), 75);
+    }
+
+    #[test]
+    fn wild_draws() {
+        let (won, drawn, empty) = ("xxx/3/3", "xoo/oxx/oxo", "3/3/3");
+        let mut board = sub_boards([
+            [won, won, drawn],
+            [empty, empty, empty],
+            [empty, empty, empty],
+        ]);
+        assert_eq!(board.get_winner(), None);
+
+        board.set_rules(Rules {
+            wild_draws: true,
+            ..Default::default()
+        });
         assert_eq!(board.get_winner(), Some(State::Cross));
+        board.set(&[(0, 2)], Element::Board(Box::new(empty.parse().unwrap())));
+        assert_eq!(board.get_winner(), None);
+        assert!(board.can_win(State::Circle));
+    }
+
+    #[test]
+    fn majority_tiebreak() {
+        let (o, x) = ("ooo/3/3", "xxx/3/3");
+        let mut board = sub_boards([[o, x, o], [o, x, x], [x, o, o]]);
+        assert!(board.is_stalemate());
+
+        let rules = Rules {
+            majority_tiebreak: true,
+            ..Default::default()
+        };
+        board.set_rules(rules);
+        assert_eq!(board.get_winner(), Some(State::Circle));
         assert!(!board.is_stalemate());
+
+        // cells are not sub-boards, so a full board of them is still drawn
+        let mut board: Board = "xoo/oxx/oxo".parse().unwrap();
+        board.set_rules(rules);
+        assert!(board.is_stalemate());
+    }
+
+    /// Checks the cached outcome of `board` and all boards below it against one worked out from
+    /// scratch with the standard rules.
+    fn check_against_reference(board: &Board) {
+        let shape = board.shape();
+        let elements = board.elements();
+        for element in elements.iter().flatten() {
+            if let Element::Board(sub_board) | Element::Won(_, sub_board) = element {
+                check_against_reference(sub_board);
+            }
+        }
+
+        // the winner of boards below was checked already
+        let owner = |(x, y): (usize, usize)| match &elements[x][y] {
+            &Element::State(state) => state,
+            Element::Board(sub_board) => sub_board.get_winner(),
+            &Element::Won(winner, _) => Some(winner),
+        };
+        let mut line_players = vec![];
+        for start_x in 0..shape.size {
+            for start_y in 0..shape.size {
+                for (dx, dy) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+                    let cells: Vec<_> = (0..shape.win_length as isize)
+                        .map(|i| (start_x as isize + dx * i, start_y as isize + dy * i))
+                        .collect();
+                    let on_board = |&(x, y): &(isize, isize)| {
+                        (0..shape.size as isize).contains(&x)
+                            && (0..shape.size as isize).contains(&y)
+                    };
+                    if !cells.iter().all(on_board) {
+                        continue;
+                    }
+                    let Some(first) = owner((start_x, start_y)) else {
+                        continue;
+                    };
+                    if cells
+                        .iter()
+                        .all(|&(x, y)| owner((x as usize, y as usize)) == Some(first))
+                        && !line_players.contains(&first)
+                    {
+                        line_players.push(first);
+                    }
+                }
+            }
+        }
+
+        // who gets the board if several players have a line depends on who got theirs first
+        match (&line_players[..], board.get_winner()) {
+            ([], winner) => assert_eq!(winner, None),
+            ([player], winner) => assert_eq!(winner, Some(*player)),
+            (players, winner) => assert!(players.contains(&winner.unwrap())),
+        }
+        let all_decided = elements.iter().flatten().all(|element| match element {
+            Element::State(state) => state.is_some(),
+            Element::Board(sub_board) => {
+                sub_board.get_winner().is_some() || sub_board.is_stalemate()
+            }
+            Element::Won(_, _) => true,
+        });
+        assert_eq!(board.is_stalemate(), line_players.is_empty() && all_decided);
+        let moves_left: usize = elements
+            .iter()
+            .flatten()
+            .map(|element| match element {
+                Element::State(state) => usize::from(state.is_none()),
+                Element::Board(sub_board) => sub_board.num_moves_left(),
+                Element::Won(_, _) => 0,
+            })
+            .sum();
+        assert_eq!(board.num_moves_left(), moves_left);
+    }
+
+    #[test]
+    fn matches_a_naive_reference() {
+        let mut rng = Rng::new(7);
+        for round in 0..60 {
+            let size = 3 + rng.below(2);
+            let shape = BoardShape {
+                size,
+                win_length: 2 + rng.below(size - 1),
+            };
+            let num_layers = 1 + round % 2;
+
+            // any cells in any order, even ones that a game would not allow
+            let mut board = Game::with_shape(num_layers, shape).board().clone();
+            for _ in 0..rng.below(40) {
+                let path: Vec<_> = (0..num_layers)
+                    .map(|_| (rng.below(size), rng.below(size)))
+                    .collect();
+                let state = [None, Some(State::Circle), Some(State::Cross)][rng.below(3)];
+                board.set(&path, Element::State(state));
+                check_against_reference(&board);
+            }
+
+            // games, which also win boards and take moves back
+            let mut game = Game::with_shape(num_layers, shape);
+            while game.outcome().is_none() {
+                let moves = game.legal_moves();
+                game.play(&moves[rng.below(moves.len())]).unwrap();
+                check_against_reference(game.board());
+                if rng.below(4) == 0 {
+                    game.undo();
+                    check_against_reference(game.board());
+                }
+            }
+        }
     }
 }
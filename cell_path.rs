@@ -0,0 +1,259 @@
+use crate::{Board, Element};
+
+/// The (x, y) indices from the root board down to an element, x goes right and y goes up.
+///
+/// Formatted as one cell number per layer, counted like the keys of a phone from the top left,
+/// so `"5.1"` is the top left cell of the center board. The alternate form (`{:#}`) names the
+/// column with a letter and the row with a number from the bottom, like `"b2/a3"`. Both forms
+/// can be parsed, and the empty path, which is the whole board, is written as `"-"`. Cell numbers
+/// only make sense on 3 by 3 boards, so paths on boards of other sizes are written and read with
+/// [`display`](Self::display) and [`parse_sized`](Self::parse_sized), which always use the
+/// alternate form for them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellPath(Vec<(usize, usize)>);
+
+impl CellPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, cell: (usize, usize)) {
+        self.0.push(cell);
+    }
+
+    pub fn pop(&mut self) -> Option<(usize, usize)> {
+        self.0.pop()
+    }
+
+    /// A new path to the given cell inside of the element at this path.
+    pub fn child(&self, cell: (usize, usize)) -> Self {
+        let mut path = self.clone();
+        path.push(cell);
+        path
+    }
+
+    /// Formats the path as cell numbers on boards with 3 cells per side and in the alternate form
+    /// on boards of any other `size`.
+    pub fn display(&self, size: usize) -> impl std::fmt::Display + '_ {
+        SizedCellPath { path: self, size }
+    }
+
+    /// Parses a path on boards with `size` cells per side, where cell numbers are only allowed
+    /// if that is 3.
+    pub fn parse_sized(s: &str, size: usize) -> Result<Self, ParseCellPathError> {
+        parse(s, size == 3)
+    }
+
+    pub fn resolve<'a>(&self, board: &'a Board) -> Option<&'a Element> {
+        let (&(x, y), rest) = self.0.split_first()?;
+        let mut element = board.elements().get(x)?.get(y)?;
+        for &(x, y) in rest {
+            element = match element {
+                Element::Board(board) | Element::Won(_, board) => {
+                    board.elements().get(x)?.get(y)?
+                }
+                Element::State(_) => return None,
+            };
+        }
+        Some(element)
+    }
+
+    /// Resolves a path that names a board rather than a cell, the empty path is the root board.
+    /// Boards that have been won can be resolved as well.
+    pub fn resolve_board<'a>(&self, board: &'a Board) -> Option<&'a Board> {
+        if self.0.is_empty() {
+            return Some(board);
+        }
+        match self.resolve(board)? {
+            Element::Board(board) | Element::Won(_, board) => Some(board),
+            Element::State(_) => None,
+        }
+    }
+
+    /// The center and size of the element at this path in world space on boards with `size`
+    /// cells per side, the root board is centered on the origin and its cells are 1 unit wide.
+    pub fn world_rect(&self, size: usize) -> (cgmath::Vector2<f32>, cgmath::Vector2<f32>) {
+        let size = size as f32;
+        let mut position = cgmath::vec2(0.0, 0.0);
+        let mut scale = cgmath::vec2(size, size);
+        for &(x, y) in &self.0 {
+            scale /= size;
+            position += cgmath::vec2(
+                (x as f32 - (size - 1.0) / 2.0) * scale.x,
+                (y as f32 - (size - 1.0) / 2.0) * scale.y,
+            );
+        }
+        (position, scale)
+    }
+
+    /// The path of the deepest element of `board` at `position` in world space.
+    pub fn from_world_position(board: &Board, position: cgmath::Vector2<f32>) -> Option<Self> {
+        let mut path = Self::new();
+        let mut board = board;
+        let mut center = cgmath::vec2(0.0, 0.0);
+        let mut scale = 1.0;
+        loop {
+            let size = board.shape().size as f32;
+            let cell = (position - center) / scale + cgmath::vec2(size / 2.0, size / 2.0);
+            if !(0.0..size).contains(&cell.x) || !(0.0..size).contains(&cell.y) {
+                return None;
+            }
+            let (x, y) = (cell.x as usize, cell.y as usize);
+            path.push((x, y));
+            match &board.elements()[x][y] {
+                Element::State(_) => return Some(path),
+                Element::Board(sub_board) | Element::Won(_, sub_board) => {
+                    board = sub_board;
+                    let offset = (size - 1.0) / 2.0;
+                    center += cgmath::vec2(x as f32 - offset, y as f32 - offset) * scale;
+                    scale /= size;
+                }
+            }
+        }
+    }
+}
+
+impl std::ops::Deref for CellPath {
+    type Target = [(usize, usize)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<(usize, usize)>> for CellPath {
+    fn from(path: Vec<(usize, usize)>) -> Self {
+        Self(path)
+    }
+}
+
+impl FromIterator<(usize, usize)> for CellPath {
+    fn from_iter<T: IntoIterator<Item = (usize, usize)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl std::fmt::Display for CellPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "-");
+        }
+        let alternate = f.alternate() || self.0.iter().any(|&(x, y)| x >= 3 || y >= 3);
+        for (i, &(x, y)) in self.0.iter().enumerate() {
+            if alternate {
+                if i > 0 {
+                    write!(f, "/")?;
+                }
+                write!(f, "{}{}", (b'a' + x as u8) as char, y + 1)?;
+            } else {
+                if i > 0 {
+                    write!(f, ".")?;
+                }
+                write!(f, "{}", (2 - y) * 3 + x + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct SizedCellPath<'a> {
+    path: &'a CellPath,
+    size: usize,
+}
+
+impl std::fmt::Display for SizedCellPath<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.size == 3 && !f.alternate() {
+            write!(f, "{}", self.path)
+        } else {
+            write!(f, "{:#}", self.path)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCellPathError(String);
+
+impl std::fmt::Display for ParseCellPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid cell `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseCellPathError {}
+
+fn parse(s: &str, numbers: bool) -> Result<CellPath, ParseCellPathError> {
+    if s == "-" {
+        return Ok(CellPath::new());
+    }
+    s.split(['.', '/'])
+        .map(|cell| match *cell.as_bytes() {
+            [n @ b'1'..=b'9'] if numbers => {
+                let n = (n - b'1') as usize;
+                Ok((n % 3, 2 - n / 3))
+            }
+            [column @ b'a'..=b'z', ref row @ ..] => match std::str::from_utf8(row)
+                .ok()
+                .filter(|row| !row.starts_with(['0', '+']))
+                .and_then(|row| row.parse::<usize>().ok())
+            {
+                Some(row) => Ok(((column - b'a') as usize, row - 1)),
+                None => Err(ParseCellPathError(cell.to_string())),
+            },
+            _ => Err(ParseCellPathError(cell.to_string())),
+        })
+        .collect()
+}
+
+/// Reads both forms, use [`CellPath::parse_sized`] for paths on boards that are not 3 by 3.
+impl std::str::FromStr for CellPath {
+    type Err = ParseCellPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_and_parsing() {
+        let path = CellPath::from(vec![(1, 1), (0, 2)]);
+        assert_eq!(path.to_string(), "5.1");
+        assert_eq!(format!("{path:#}"), "b2/a3");
+        assert_eq!("5.1".parse(), Ok(path.clone()));
+        assert_eq!("b2/a3".parse(), Ok(path));
+        assert_eq!("-".parse(), Ok(CellPath::new()));
+        assert!("5.0".parse::<CellPath>().is_err());
+        assert!("a0".parse::<CellPath>().is_err());
+        assert!("b".parse::<CellPath>().is_err());
+
+        let path = CellPath::from(vec![(3, 0), (0, 11)]);
+        assert_eq!(path.to_string(), "d1/a12");
+        assert_eq!("d1/a12".parse(), Ok(path));
+
+        // cell numbers only name cells on 3 by 3 boards
+        let path = CellPath::from(vec![(0, 3)]);
+        assert_eq!(path.display(4).to_string(), "a4");
+        assert_eq!(CellPath::from(vec![(0, 2)]).display(4).to_string(), "a3");
+        assert_eq!(CellPath::parse_sized("a4", 4), Ok(path));
+        assert!(CellPath::parse_sized("1", 4).is_err());
+        assert_eq!(CellPath::parse_sized("1", 3), "a3".parse());
+    }
+
+    #[test]
+    fn world_position_round_trip() {
+        let mut board = Board::default();
+        board.set(&[(2, 0)], Element::Board(Box::default()));
+        let path = CellPath::from(vec![(2, 0), (1, 2)]);
+        let (center, size) = path.world_rect(3);
+        assert_eq!(size, cgmath::vec2(1.0 / 3.0, 1.0 / 3.0));
+        assert_eq!(CellPath::from_world_position(&board, center), Some(path));
+        assert_eq!(
+            CellPath::from_world_position(&board, cgmath::vec2(2.0, 0.0)),
+            None
+        );
+    }
+}
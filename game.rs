@@ -0,0 +1,644 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    get_playable_board, get_target_board, is_move_in_target, Board, BoardShape, CellPath, Element,
+    Position, Rules, State,
+};
+
+/// The path of the cell that is played in.
+pub type Move = CellPath;
+
+/// Where the versions of all games come from, so that no two boards share one.
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Winner(State),
+    Stalemate,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Winner(winner) => write!(f, "{winner} won the game"),
+            Outcome::Stalemate => write!(f, "Stalemate"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    GameOver,
+    InvalidCell,
+    CellTaken,
+    BoardAlreadyWon,
+    OutsideTargetBoard,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::GameOver => write!(f, "the game is already over"),
+            MoveError::InvalidCell => write!(f, "the move does not name a cell on the board"),
+            MoveError::CellTaken => write!(f, "the cell has already been played in"),
+            MoveError::BoardAlreadyWon => write!(f, "the cell is on a board that was already won"),
+            MoveError::OutsideTargetBoard => {
+                write!(
+                    f,
+                    "the move is outside of the board that has to be played in"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    mv: Move,
+    player: State,
+    /// Boards that were won by this move, which have to be turned back into boards on undo.
+    won_boards: Vec<CellPath>,
+}
+
+/// The rules of the game without any rendering, the [`App`](crate::App) is only a view over this.
+#[derive(Debug, Clone)]
+pub struct Game {
+    board: Board,
+    num_layers: usize,
+    turn: State,
+    history: Vec<HistoryEntry>,
+    /// Moves that were undone, the next one to redo is last.
+    redo_stack: Vec<Move>,
+    /// The position the game was set up from, `None` if it started on an empty board.
+    start: Option<Position>,
+    /// Whether the game ends in a stalemate as soon as neither player can get a line anymore,
+    /// rather than when the board is full.
+    early_draws: bool,
+    /// See [`start_version`](Self::start_version).
+    start_version: u64,
+    /// See [`version`](Self::version).
+    version: u64,
+}
+
+impl Game {
+    pub fn new(num_layers: usize) -> Self {
+        Self::with_shape(num_layers, BoardShape::default())
+    }
+
+    pub fn with_shape(num_layers: usize, shape: BoardShape) -> Self {
+        let version = next_version();
+        Self {
+            board: Self::new_board(num_layers, shape),
+            num_layers,
+            turn: State::Circle,
+            history: vec![],
+            redo_stack: vec![],
+            start: None,
+            early_draws: false,
+            start_version: version,
+            version,
+        }
+    }
+
+    /// A game that continues from `position` instead of an empty board.
+    pub fn from_position(position: Position) -> Self {
+        let version = next_version();
+        Self {
+            board: position.board.clone(),
+            num_layers: position.num_layers(),
+            turn: position.turn,
+            history: vec![],
+            redo_stack: vec![],
+            start: Some(position),
+            early_draws: false,
+            start_version: version,
+            version,
+        }
+    }
+
+    /// The current position, which can be written down to set up the same game again later.
+    pub fn position(&self) -> Position {
+        Position {
+            board: self.board.clone(),
+            turn: self.turn,
+            target: self.target_board(),
+        }
+    }
+
+    pub fn start_position(&self) -> Option<&Position> {
+        self.start.as_ref()
+    }
+
+    pub fn early_draws(&self) -> bool {
+        self.early_draws
+    }
+
+    pub fn set_early_draws(&mut self, early_draws: bool) {
+        self.early_draws = early_draws;
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// A number that changes whenever the board does, and that no other board had, so views of
+    /// the board can tell that it is still the same without looking at it.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Like [`version`](Self::version), but for the board the moves were played on, which only
+    /// changes when the game starts over or the rules change. Games with the same start version
+    /// only differ in their moves.
+    pub fn start_version(&self) -> u64 {
+        self.start_version
+    }
+
+    pub fn num_layers(&self) -> usize {
+        self.num_layers
+    }
+
+    pub fn shape(&self) -> BoardShape {
+        self.board.shape()
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.board.rules()
+    }
+
+    /// Plays the game with other rules from the start, which can only be chosen before the first
+    /// move.
+    pub fn set_rules(&mut self, rules: Rules) {
+        assert!(
+            self.history.is_empty(),
+            "the rules can only be changed before the first move"
+        );
+        self.board.set_rules(rules);
+        if let Some(start) = &mut self.start {
+            start.board.set_rules(rules);
+        }
+        self.redo_stack.clear();
+        self.start_version = next_version();
+        self.version = self.start_version;
+    }
+
+    pub fn current_player(&self) -> State {
+        self.turn
+    }
+
+    pub fn num_moves(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn num_moves_left(&self) -> usize {
+        self.board.num_moves_left()
+    }
+
+    /// The moves played so far along with who played them, oldest first.
+    pub fn moves(&self) -> impl Iterator<Item = (State, &Move)> + '_ {
+        self.history.iter().map(|entry| (entry.player, &entry.mv))
+    }
+
+    /// The moves that were undone and can be redone, in the order they would be redone.
+    pub fn redo_moves(&self) -> impl Iterator<Item = &Move> + '_ {
+        self.redo_stack.iter().rev()
+    }
+
+    pub fn last_move(&self) -> Option<&Move> {
+        self.history.last().map(|entry| &entry.mv)
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        if let Some(winner) = self.board.get_winner() {
+            Some(Outcome::Winner(winner))
+        } else if self.board.is_stalemate() || (self.early_draws && self.board.is_dead()) {
+            Some(Outcome::Stalemate)
+        } else {
+            None
+        }
+    }
+
+    /// The path of the board the next move has to be played in, see [`get_target_board`].
+    pub fn target_board(&self) -> CellPath {
+        match (self.last_move(), &self.start) {
+            (None, Some(start)) => get_playable_board(&self.board, start.target.clone()),
+            (last_move, _) => get_target_board(&self.board, last_move),
+        }
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        fn collect_moves(board: &Board, path: &mut Move, moves: &mut Vec<Move>) {
+            for (x, column) in board.elements().iter().enumerate() {
+                for (y, element) in column.iter().enumerate() {
+                    path.push((x, y));
+                    match element {
+                        Element::State(None) => moves.push(path.clone()),
+                        Element::Board(board) => collect_moves(board, path, moves),
+                        Element::Won(_, board) if board.rules().play_in_won_boards => {
+                            collect_moves(board, path, moves)
+                        }
+                        Element::State(Some(_)) | Element::Won(_, _) => {}
+                    }
+                    path.pop();
+                }
+            }
+        }
+
+        if self.outcome().is_some() {
+            return vec![];
+        }
+
+        let mut path = self.target_board();
+        let board = path
+            .resolve_board(&self.board)
+            .expect("the target board is always playable");
+
+        let mut moves = vec![];
+        collect_moves(board, &mut path, &mut moves);
+        moves
+    }
+
+    /// Plays a move for the current player. Playing anything other than the next move that
+    /// could be redone clears the redo history.
+    pub fn play(&mut self, mv: &Move) -> Result<(), MoveError> {
+        self.apply(mv)?;
+        if self.redo_stack.last() == Some(mv) {
+            self.redo_stack.pop();
+        } else {
+            self.redo_stack.clear();
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, mv: &Move) -> Result<(), MoveError> {
+        if self.outcome().is_some() {
+            return Err(MoveError::GameOver);
+        }
+
+        match mv.resolve(&self.board) {
+            Some(Element::State(None)) => {}
+            Some(Element::State(Some(_))) => return Err(MoveError::CellTaken),
+            Some(Element::Board(_) | Element::Won(_, _)) | None => {
+                return Err(MoveError::InvalidCell)
+            }
+        }
+
+        if !self.rules().play_in_won_boards && Self::is_inside_won_board(&self.board, mv) {
+            return Err(MoveError::BoardAlreadyWon);
+        }
+
+        if !is_move_in_target(mv, &self.target_board()) {
+            return Err(MoveError::OutsideTargetBoard);
+        }
+
+        self.board.set(mv, Element::State(Some(self.turn)));
+        let won_boards = Self::mark_won_boards(&mut self.board, mv);
+
+        self.history.push(HistoryEntry {
+            mv: mv.clone(),
+            player: self.turn,
+            won_boards,
+        });
+        self.turn = self.turn.next(self.rules().num_players);
+        self.version = next_version();
+        Ok(())
+    }
+
+    /// Takes back the last move, including any boards that it won.
+    pub fn undo(&mut self) -> Option<Move> {
+        let HistoryEntry {
+            mv,
+            player,
+            won_boards,
+        } = self.history.pop()?;
+
+        for path in won_boards.into_iter().rev() {
+            if let Element::Won(_, board) = self.board.set(&path, Element::default()) {
+                self.board.set(&path, Element::Board(board));
+            }
+        }
+        self.board.set(&mv, Element::State(None));
+
+        self.turn = player;
+        self.version = next_version();
+        self.redo_stack.push(mv.clone());
+        Some(mv)
+    }
+
+    /// Plays the last undone move again.
+    pub fn redo(&mut self) -> Option<Move> {
+        let mv = self.redo_stack.pop()?;
+        self.apply(&mv)
+            .expect("undone moves are legal when they are redone");
+        Some(mv)
+    }
+
+    /// Undoes or redoes moves until `num_moves` moves have been played. Returns false if there
+    /// are not enough moves to redo.
+    pub fn jump_to(&mut self, num_moves: usize) -> bool {
+        if num_moves > self.history.len() + self.redo_stack.len() {
+            return false;
+        }
+        while self.history.len() > num_moves {
+            self.undo();
+        }
+        while self.history.len() < num_moves {
+            self.redo();
+        }
+        true
+    }
+
+    /// The number of ways to play `depth` more moves, where games that end sooner count for
+    /// nothing. Like the function of the same name for chess engines, the known counts check
+    /// that exactly the legal moves are generated.
+    pub fn perft(&self, depth: usize) -> u64 {
+        self.clone().count_leaves(depth)
+    }
+
+    fn count_leaves(&mut self, depth: usize) -> u64 {
+        let moves = self.legal_moves();
+        match depth {
+            0 => 1,
+            1 => moves.len() as u64,
+            _ => moves
+                .iter()
+                .map(|mv| {
+                    self.apply(mv).unwrap();
+                    let count = self.count_leaves(depth - 1);
+                    self.undo();
+                    count
+                })
+                .sum(),
+        }
+    }
+
+    fn is_inside_won_board(board: &Board, mv: &Move) -> bool {
+        let mut board = board;
+        for &(x, y) in mv.iter() {
+            match &board.elements()[x][y] {
+                Element::State(_) => break,
+                Element::Board(sub_board) => board = sub_board,
+                Element::Won(_, _) => return true,
+            }
+        }
+        false
+    }
+
+    /// Turns the boards around `mv` that it won into [`Element::Won`] and returns their paths,
+    /// innermost first.
+    fn mark_won_boards(board: &mut Board, mv: &Move) -> Vec<CellPath> {
+        let mut won_boards = vec![];
+        let mut path = mv.clone();
+        // a move can win boards on several layers at once
+        while path.pop().is_some() && !path.is_empty() {
+            if let Some(Element::Board(sub_board)) = path.resolve(board) {
+                if let Some(winner) = sub_board.get_winner() {
+                    if let Element::Board(sub_board) = board.set(&path, Element::default()) {
+                        board.set(&path, Element::Won(winner, sub_board));
+                    }
+                    won_boards.push(path.clone());
+                }
+            }
+        }
+        won_boards
+    }
+
+    fn new_board(num_layers: usize, shape: BoardShape) -> Board {
+        assert!(num_layers > 0);
+        let mut board = Board::new(shape);
+        if num_layers > 1 {
+            for x in 0..shape.size {
+                for y in 0..shape.size {
+                    let sub_board = Self::new_board(num_layers - 1, shape);
+                    board.set(&[(x, y)], Element::Board(Box::new(sub_board)));
+                }
+            }
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Known numbers of move sequences of each length from the start, for 1, 2 and 3 layers.
+    /// Two layers are the numbers that have been published for Ultimate Tic Tac Toe, and the
+    /// board tree and the [`BitBoard`](crate::BitBoard) agree on all of them.
+    const PERFT: [&[u64]; 3] = [
+        &[9, 72, 504, 3024, 15120, 54720, 148176, 200448, 127872],
+        &[81, 720, 6336, 55080, 473256, 4020960, 33782544],
+        &[729, 6552, 58824, 527472, 4723992, 42255576],
+    ];
+
+    #[test]
+    fn perft() {
+        for (layers, counts) in PERFT.iter().enumerate() {
+            let game = Game::new(layers + 1);
+            // the deeper counts take too long for a test
+            for (depth, &count) in counts
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count < 60_000)
+            {
+                assert_eq!(game.perft(depth + 1), count, "{} layers", layers + 1);
+            }
+        }
+        assert_eq!(Game::new(2).perft(0), 1);
+    }
+
+    #[test]
+    fn legal_moves_follow_target_board() {
+        let mut game = Game::new(2);
+        assert_eq!(game.legal_moves().len(), 81);
+        game.play(&"1.6".parse().unwrap()).unwrap();
+        assert_eq!(game.current_player(), State::Cross);
+        assert_eq!(game.legal_moves().len(), 9);
+        assert!(game.legal_moves().iter().all(|mv| mv[0] == (2, 1)));
+        assert_eq!(
+            game.play(&"5.7".parse().unwrap()),
+            Err(MoveError::OutsideTargetBoard)
+        );
+        assert_eq!(
+            game.play(&vec![(2, 1), (3, 0)].into()),
+            Err(MoveError::InvalidCell)
+        );
+        assert_eq!(
+            game.play(&"1.6".parse().unwrap()),
+            Err(MoveError::CellTaken)
+        );
+    }
+
+    #[test]
+    fn undo_restores_won_board() {
+        let mut game = Game::new(2);
+        let moves: Vec<Move> = ["5.7", "7.5", "5.8", "8.5", "5.9"]
+            .iter()
+            .map(|mv| mv.parse().unwrap())
+            .collect();
+        for mv in &moves {
+            game.play(mv).unwrap();
+        }
+        assert!(matches!(
+            game.board().elements()[1][1],
+            Element::Won(State::Circle, _)
+        ));
+        assert_eq!(
+            game.play(&"5.1".parse().unwrap()),
+            Err(MoveError::BoardAlreadyWon)
+        );
+
+        assert_eq!(game.undo().as_ref(), Some(&moves[4]));
+        assert_eq!(game.current_player(), State::Circle);
+        match &game.board().elements()[1][1] {
+            Element::Board(board) => {
+                assert!(matches!(
+                    board.elements()[0][0],
+                    Element::State(Some(State::Circle))
+                ));
+                assert!(matches!(
+                    board.elements()[1][0],
+                    Element::State(Some(State::Circle))
+                ));
+                assert!(matches!(board.elements()[2][0], Element::State(None)));
+            }
+            _ => panic!("undo did not restore the won board"),
+        }
+        assert_eq!(game.num_moves_left(), 77);
+
+        assert_eq!(game.redo().as_ref(), Some(&moves[4]));
+        assert_eq!(game.outcome(), None);
+        assert!(game.jump_to(1));
+        assert_eq!(game.redo_moves().count(), 4);
+        assert!(!game.jump_to(6));
+        game.play(&moves[1]).unwrap();
+        assert_eq!(game.redo_moves().count(), 3);
+        game.play(&"5.1".parse().unwrap()).unwrap();
+        assert_eq!(game.redo_moves().count(), 0);
+        assert_eq!(game.moves().count(), 3);
+    }
+
+    #[test]
+    fn one_move_wins_several_layers() {
+        let e = "(3/3/3)";
+        let empty = format!("({e}{e}{e}/{e}{e}{e}/{e}{e}{e})");
+        let almost_won = format!("({e}{e}{e}/{e}{e}{e}/(ooo/3/3)(ooo/3/3)(x1o/x1o/3))");
+        let position: Position =
+            format!("{empty}{empty}{empty}/{empty}{empty}{empty}/{almost_won}{empty}{empty} o 7.9")
+                .parse()
+                .unwrap();
+        let mut game = Game::from_position(position);
+        game.play(&"7.9.9".parse().unwrap()).unwrap();
+        match &game.board().elements()[0][0] {
+            Element::Won(State::Circle, board) => assert!(matches!(
+                board.elements()[2][0],
+                Element::Won(State::Circle, _)
+            )),
+            _ => panic!("the outer board was not won"),
+        }
+        game.undo();
+        assert!(matches!(game.board().elements()[0][0], Element::Board(_)));
+    }
+
+    #[test]
+    fn play_in_won_boards() {
+        let mut game = Game::new(2);
+        game.set_rules(Rules {
+            play_in_won_boards: true,
+            ..Default::default()
+        });
+        for mv in ["5.7", "7.5", "5.8", "8.5", "5.9", "9.5"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        assert!(matches!(
+            game.board().elements()[1][1],
+            Element::Won(State::Circle, _)
+        ));
+        assert_eq!(game.target_board(), "5".parse().unwrap());
+        assert_eq!(game.legal_moves().len(), 6);
+        game.play(&"5.1".parse().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn three_players() {
+        let mut game = Game::new(1);
+        game.set_rules(Rules {
+            num_players: 3,
+            ..Default::default()
+        });
+        for mv in ["1", "2", "7", "3", "4", "8", "5", "6"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        assert_eq!(game.current_player(), State::Triangle);
+        game.play(&"9".parse().unwrap()).unwrap();
+        assert_eq!(game.outcome(), Some(Outcome::Winner(State::Triangle)));
+        game.undo();
+        assert_eq!(game.current_player(), State::Triangle);
+        assert_eq!(game.position().to_string(), "oxo/xox/tt1 t -");
+    }
+
+    #[test]
+    fn bigger_boards() {
+        let shape = BoardShape {
+            size: 4,
+            win_length: 3,
+        };
+        let mut game = Game::with_shape(2, shape);
+        assert_eq!(game.legal_moves().len(), 256);
+        game.play(&"b1/d4".parse().unwrap()).unwrap();
+        assert_eq!(game.target_board(), "d4".parse().unwrap());
+        assert_eq!(game.legal_moves().len(), 16);
+        assert_eq!(
+            game.play(&"d4/e1".parse().unwrap()),
+            Err(MoveError::InvalidCell)
+        );
+        for mv in ["d4/a1", "a1/d4", "d4/b2", "b2/d4", "d4/c3"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        assert!(matches!(
+            game.board().elements()[3][3],
+            Element::Won(State::Cross, _)
+        ));
+    }
+
+    #[test]
+    fn early_draw() {
+        let mut game = Game::from_position("xo1/oxx/ox1 o -".parse().unwrap());
+        game.play(&"9".parse().unwrap()).unwrap();
+        assert_eq!(game.outcome(), None);
+        game.set_early_draws(true);
+        assert_eq!(game.outcome(), Some(Outcome::Stalemate));
+        assert!(game.legal_moves().is_empty());
+        game.undo();
+        assert_eq!(game.outcome(), None);
+    }
+
+    #[test]
+    fn continue_from_position() {
+        let position: Position =
+            "(3/3/3)(o2/3/3)(3/3/3)/(3/3/3)(3/3/3)(3/3/3)/(3/3/3)(3/3/3)(3/3/3) x 1"
+                .parse()
+                .unwrap();
+        let mut game = Game::from_position(position.clone());
+        assert_eq!(game.num_layers(), 2);
+        assert_eq!(game.current_player(), State::Cross);
+        assert_eq!(game.num_moves_left(), 80);
+        assert_eq!(game.target_board(), "1".parse().unwrap());
+        assert_eq!(game.legal_moves().len(), 9);
+
+        game.play(&"1.2".parse().unwrap()).unwrap();
+        assert_eq!(game.target_board(), "2".parse().unwrap());
+        assert_eq!(
+            game.position().to_string(),
+            "(1x1/3/3)(o2/3/3)(3/3/3)/(3/3/3)(3/3/3)(3/3/3)/(3/3/3)(3/3/3)(3/3/3) o 2"
+        );
+        game.undo();
+        assert_eq!(game.position().to_string(), position.to_string());
+    }
+}
@@ -1,13 +1,16 @@
 use ultimate_tic_tac_toe::App;
 
 fn main() {
+    // a saved game can be passed to open it
+    let path = std::env::args_os().nth(1).map(std::path::PathBuf::from);
+
     eframe::run_native(
         "Ultimate Tic Tac Toe",
         eframe::NativeOptions {
             renderer: eframe::Renderer::Wgpu,
             ..Default::default()
         },
-        Box::new(|cc| Box::new(App::new(cc))),
+        Box::new(move |cc| Box::new(App::new(cc, path))),
     )
     .unwrap();
 }
@@ -0,0 +1,271 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{Game, Move, Outcome, State};
+
+/// Monte Carlo tree search using UCT to pick moves and random playouts to score them.
+///
+/// Every thread grows its own tree from the root, seeded from `seed` and its index, and the
+/// statistics of the root moves are added up in the end, so the same settings always give the
+/// same result.
+#[derive(Debug, Clone, Copy)]
+pub struct Mcts {
+    pub seed: u64,
+    /// The number of playouts over all threads.
+    pub playouts: u64,
+    pub threads: usize,
+    pub exploration: f64,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            playouts: 10_000,
+            threads: 1,
+            exploration: std::f64::consts::SQRT_2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveStats {
+    pub mv: Move,
+    pub visits: u64,
+    /// The summed results of the playouts through this move for the player making it, where a
+    /// win counts 1 and a stalemate counts a half.
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MctsResult {
+    /// The most visited move.
+    pub best_move: Option<Move>,
+    pub stats: Vec<MoveStats>,
+}
+
+impl Mcts {
+    pub fn search(&self, game: &Game) -> MctsResult {
+        self.search_with_stop(game, &AtomicBool::new(false))
+    }
+
+    /// Like [`search`](Self::search), but ends early once `stop` is set, with the playouts made
+    /// so far.
+    pub fn search_with_stop(&self, game: &Game, stop: &AtomicBool) -> MctsResult {
+        let threads = self.threads.max(1);
+        let trees: Vec<Vec<MoveStats>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|thread| {
+                    let playouts = self.playouts / threads as u64
+                        + u64::from((thread as u64) < self.playouts % threads as u64);
+                    let mut rng = Rng::new(self.seed.wrapping_add(thread as u64));
+                    let mut game = game.clone();
+                    scope.spawn(move || self.search_tree(&mut game, playouts, &mut rng, stop))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut stats: Vec<MoveStats> = vec![];
+        for tree in trees {
+            for move_stats in tree {
+                match stats.iter_mut().find(|stats| stats.mv == move_stats.mv) {
+                    Some(stats) => {
+                        stats.visits += move_stats.visits;
+                        stats.score += move_stats.score;
+                    }
+                    None => stats.push(move_stats),
+                }
+            }
+        }
+
+        MctsResult {
+            best_move: stats
+                .iter()
+                .max_by_key(|stats| stats.visits)
+                .map(|stats| stats.mv.clone()),
+            stats,
+        }
+    }
+
+    fn search_tree(
+        &self,
+        game: &mut Game,
+        playouts: u64,
+        rng: &mut Rng,
+        stop: &AtomicBool,
+    ) -> Vec<MoveStats> {
+        let mut nodes = vec![Node::new(None, None, game)];
+        for _ in 0..playouts {
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            let mut depth = 0;
+
+            // selection
+            let mut node = 0;
+            while nodes[node].untried.is_empty() && !nodes[node].children.is_empty() {
+                let parent_visits = (nodes[node].visits as f64).ln();
+                node = *nodes[node]
+                    .children
+                    .iter()
+                    .max_by(|&&a, &&b| {
+                        let uct = |node: &Node| {
+                            node.score / node.visits as f64
+                                + self.exploration * (parent_visits / node.visits as f64).sqrt()
+                        };
+                        uct(&nodes[a]).total_cmp(&uct(&nodes[b]))
+                    })
+                    .unwrap();
+                game.play(nodes[node].mv.as_ref().unwrap()).unwrap();
+                depth += 1;
+            }
+
+            // expansion
+            if !nodes[node].untried.is_empty() {
+                let index = rng.below(nodes[node].untried.len());
+                let mv = nodes[node].untried.swap_remove(index);
+                let player = game.current_player();
+                game.play(&mv).unwrap();
+                depth += 1;
+                nodes.push(Node::new(Some(mv), Some((node, player)), game));
+                let child = nodes.len() - 1;
+                nodes[node].children.push(child);
+                node = child;
+            }
+
+            // playout
+            let outcome = loop {
+                if let Some(outcome) = game.outcome() {
+                    break outcome;
+                }
+                let mut moves = game.legal_moves();
+                let mv = moves.swap_remove(rng.below(moves.len()));
+                game.play(&mv).unwrap();
+                depth += 1;
+            };
+            for _ in 0..depth {
+                game.undo();
+            }
+
+            // backpropagation
+            let mut node = Some(node);
+            while let Some(index) = node {
+                let current = &mut nodes[index];
+                current.visits += 1;
+                current.score += match (outcome, current.player) {
+                    (Outcome::Winner(winner), Some(player)) if winner == player => 1.0,
+                    (Outcome::Stalemate, _) => 0.5,
+                    _ => 0.0,
+                };
+                node = current.parent;
+            }
+        }
+
+        nodes[0]
+            .children
+            .iter()
+            .map(|&child| MoveStats {
+                mv: nodes[child].mv.clone().unwrap(),
+                visits: nodes[child].visits,
+                score: nodes[child].score,
+            })
+            .collect()
+    }
+}
+
+struct Node {
+    mv: Option<Move>,
+    parent: Option<usize>,
+    /// The player that made the move leading to this node.
+    player: Option<State>,
+    children: Vec<usize>,
+    untried: Vec<Move>,
+    visits: u64,
+    score: f64,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, parent: Option<(usize, State)>, game: &Game) -> Self {
+        Self {
+            mv,
+            parent: parent.map(|(parent, _)| parent),
+            player: parent.map(|(_, player)| player),
+            children: vec![],
+            untried: game.legal_moves(),
+            visits: 0,
+            score: 0.0,
+        }
+    }
+}
+
+/// A small SplitMix64 generator, so seeded searches give the same results on every platform.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A random number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_winning_move() {
+        let mut game = Game::new(1);
+        for mv in ["1", "4", "2", "5"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        let result = Mcts {
+            playouts: 2_000,
+            ..Default::default()
+        }
+        .search(&game);
+        assert_eq!(result.best_move, Some("3".parse().unwrap()));
+    }
+
+    #[test]
+    fn same_seed_same_result() {
+        let game = Game::new(2);
+        let mcts = Mcts {
+            seed: 7,
+            playouts: 500,
+            threads: 2,
+            ..Default::default()
+        };
+        let a = mcts.search(&game);
+        let b = mcts.search(&game);
+        assert_eq!(a.stats, b.stats);
+        assert_eq!(a.stats.iter().map(|stats| stats.visits).sum::<u64>(), 500);
+    }
+
+    #[test]
+    fn stopped_search_gives_up() {
+        let stop = AtomicBool::new(true);
+        let result = Mcts {
+            playouts: 1_000_000,
+            ..Default::default()
+        }
+        .search_with_stop(&Game::new(3), &stop);
+        assert!(result.stats.is_empty());
+        assert_eq!(result.best_move, None);
+    }
+}
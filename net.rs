@@ -0,0 +1,797 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::{
+    Board, Element, Game, LoadError, Move, MoveError, ParseCellPathError, SaveFile, State,
+};
+
+/// How long joining waits for the host to answer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The most lines a [`Message::Game`] can have, so a broken peer can't make us read forever.
+const MAX_GAME_LINES: usize = 64;
+
+/// The longest line of a message, which leaves room for the moves of a game that fills the
+/// biggest board there is, see [`BoardShape::MAX_CELLS`](crate::BoardShape::MAX_CELLS).
+const MAX_LINE_LEN: u64 = 16 << 20;
+
+/// Adds the next line to `line` like [`BufRead::read_line`], but fails on lines longer than
+/// [`MAX_LINE_LEN`] rather than reading all of them.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<usize, NetError> {
+    let read = reader.by_ref().take(MAX_LINE_LEN).read_line(line)?;
+    if read as u64 == MAX_LINE_LEN && !line.ends_with('\n') {
+        return Err(NetError::InvalidMessage(format!(
+            "a line longer than {MAX_LINE_LEN} bytes"
+        )));
+    }
+    Ok(read)
+}
+
+/// A message between two [`NetSession`]s.
+///
+/// Every message is one line of text, except for the saved game that follows a `game` line:
+///
+/// ```text
+/// hello 1
+/// game Circle 4
+/// ultimate-tic-tac-toe 1
+/// layers 2
+/// turn Cross
+/// moves 5.7
+/// move 7.5 9f3c0b6a2e41d857
+/// resync
+/// ```
+///
+/// Both sides start with `hello` and the version of the protocol they speak, and hang up on any
+/// other version. The host then sends the whole game as a [`SaveFile`] with the number of its
+/// lines, along with the player the host plays. Every move after that is sent with the
+/// [`board_hash`] of the board after it, so a peer whose board ends up different can tell. The
+/// host's game is the one that counts, so a joiner that lost track of it asks for it again with
+/// `resync`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Hello {
+        version: u32,
+    },
+    Game {
+        host_player: State,
+        save_file: Box<SaveFile>,
+    },
+    Move {
+        mv: Move,
+        hash: u64,
+    },
+    Resync,
+}
+
+impl Message {
+    pub const VERSION: u32 = 1;
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Message::Hello { version } => writeln!(writer, "hello {version}")?,
+            Message::Game {
+                host_player,
+                save_file,
+            } => {
+                let text = save_file.to_string();
+                writeln!(writer, "game {host_player} {}", text.lines().count())?;
+                for line in text.lines() {
+                    writeln!(writer, "{line}")?;
+                }
+            }
+            // the alternate form names cells the same way on boards of every size
+            Message::Move { mv, hash } => writeln!(writer, "move {mv:#} {hash:016x}")?,
+            Message::Resync => writeln!(writer, "resync")?,
+        }
+        writer.flush()
+    }
+
+    /// Reads the next message, or `None` once the other side hung up.
+    pub fn read_from(reader: &mut impl BufRead) -> Result<Option<Self>, NetError> {
+        let mut line = String::new();
+        if read_line(reader, &mut line)? == 0 {
+            return Ok(None);
+        }
+        let invalid = || NetError::InvalidMessage(line.trim().to_string());
+        let mut words = line.split_whitespace();
+        let message = match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("hello"), Some(version), None, None) => Message::Hello {
+                version: version.parse().map_err(|_| invalid())?,
+            },
+            (Some("game"), Some(host_player), Some(num_lines), None) => {
+                let host_player = host_player.parse().map_err(|_| invalid())?;
+                let num_lines: usize = num_lines.parse().map_err(|_| invalid())?;
+                if num_lines > MAX_GAME_LINES {
+                    return Err(invalid());
+                }
+                let mut text = String::new();
+                for _ in 0..num_lines {
+                    if read_line(reader, &mut text)? == 0 {
+                        return Ok(None);
+                    }
+                }
+                Message::Game {
+                    host_player,
+                    save_file: Box::new(text.parse().map_err(NetError::InvalidGame)?),
+                }
+            }
+            (Some("move"), Some(mv), Some(hash), None) => Message::Move {
+                mv: mv.parse().map_err(NetError::InvalidMove)?,
+                hash: u64::from_str_radix(hash, 16).map_err(|_| invalid())?,
+            },
+            (Some("resync"), None, None, None) => Message::Resync,
+            _ => return Err(invalid()),
+        };
+        Ok(Some(message))
+    }
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(std::io::Error),
+    Disconnected,
+    InvalidMessage(String),
+    UnsupportedVersion(u32),
+    InvalidGame(LoadError),
+    InvalidMove(ParseCellPathError),
+    IllegalMove(Move, MoveError),
+    /// The other side sent a move while it was this player's turn, who they don't play.
+    NotTheirTurn(State),
+}
+
+impl std::fmt::Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetError::Io(error) => write!(f, "{error}"),
+            NetError::Disconnected => write!(f, "the other player disconnected"),
+            NetError::InvalidMessage(message) => write!(f, "invalid message \"{message}\""),
+            NetError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "the other player speaks version {version} of the protocol"
+                )
+            }
+            NetError::InvalidGame(error) => write!(f, "the game that was sent is invalid: {error}"),
+            NetError::InvalidMove(error) => write!(f, "{error}"),
+            NetError::IllegalMove(mv, error) => write!(f, "move {mv} is illegal: {error}"),
+            NetError::NotTheirTurn(player) => {
+                write!(f, "the other player moved during {player}'s turn")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<std::io::Error> for NetError {
+    fn from(error: std::io::Error) -> Self {
+        NetError::Io(error)
+    }
+}
+
+/// A hash of every cell of `board` that is the same on every machine, unlike the hashers of std.
+pub fn board_hash(board: &Board) -> u64 {
+    // FNV-1a
+    fn add(hash: &mut u64, byte: u8) {
+        *hash ^= u64::from(byte);
+        *hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    fn add_board(hash: &mut u64, board: &Board) {
+        for element in board.elements().iter().flatten() {
+            match element {
+                Element::State(None) => add(hash, 0),
+                &Element::State(Some(state)) => add(hash, 1 + state as u8),
+                Element::Board(board) => {
+                    add(hash, 5);
+                    add_board(hash, board);
+                }
+                &Element::Won(winner, ref board) => {
+                    add(hash, 6 + winner as u8);
+                    add_board(hash, board);
+                }
+            }
+        }
+    }
+
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    add_board(&mut hash, board);
+    hash
+}
+
+/// One end of a connection, with the messages from the other end read on another thread.
+struct Peer {
+    stream: TcpStream,
+    receiver: mpsc::Receiver<Result<Message, NetError>>,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let message = match Message::read_from(&mut reader) {
+                Ok(Some(message)) => Ok(message),
+                Ok(None) => break,
+                Err(error) => Err(error),
+            };
+            let failed = message.is_err();
+            // the receiver is gone once the peer is dropped
+            if sender.send(message).is_err() || failed {
+                break;
+            }
+        });
+        let mut peer = Self { stream, receiver };
+        peer.send(&Message::Hello {
+            version: Message::VERSION,
+        })?;
+        Ok(peer)
+    }
+
+    fn send(&mut self, message: &Message) -> std::io::Result<()> {
+        message.write_to(&mut self.stream)
+    }
+
+    /// The next message if one arrived, or [`NetError::Disconnected`] once the connection is
+    /// gone.
+    fn poll(&self) -> Option<Result<Message, NetError>> {
+        match self.receiver.try_recv() {
+            Ok(message) => Some(message),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(NetError::Disconnected)),
+        }
+    }
+}
+
+impl std::fmt::Debug for Peer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Peer")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        // ends the reading thread
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+#[derive(Debug)]
+enum Role {
+    Host(TcpListener),
+    Join(SocketAddr),
+}
+
+/// Something that happened in a [`NetSession`] that the ui should show.
+#[derive(Debug)]
+pub enum NetEvent {
+    Connected,
+    Disconnected,
+    /// The host sent the game, which replaces the one of the joiner.
+    NewGame(Box<Game>),
+    /// The boards did not match after a move, so the game was sent again.
+    Desync,
+    /// The connection was dropped because of this.
+    Error(NetError),
+}
+
+impl std::fmt::Display for NetEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetEvent::Connected => write!(f, "Connected"),
+            NetEvent::Disconnected => write!(f, "The other player disconnected"),
+            NetEvent::NewGame(_) => write!(f, "Received the game"),
+            NetEvent::Desync => write!(f, "The boards got out of sync, syncing again"),
+            NetEvent::Error(error) => write!(f, "Network error: {error}"),
+        }
+    }
+}
+
+/// A game between two machines, where the host plays one player and the joiner all others.
+///
+/// Moves are played on the [`Game`] of each side as usual and [`update`](Self::update) sends
+/// them on. Changes to the game of the host that are not new moves, like undoing or resetting,
+/// send the whole game to the joiner, while the joiner gets the game of the host back for them.
+/// A host keeps listening after the joiner disconnects, and a joiner that joins again continues
+/// the game where it stopped.
+#[derive(Debug)]
+pub struct NetSession {
+    role: Role,
+    host_player: State,
+    peer: Option<Peer>,
+    /// A joiner that connected while the peer still was, which only replaces it once it said
+    /// hello with the same version.
+    pending: Option<Peer>,
+    /// Whether the other side said hello with the same version.
+    greeted: bool,
+    /// The game as both sides know it, `None` until the joiner got it.
+    shared: Option<SaveFile>,
+}
+
+impl NetSession {
+    /// Waits for a joiner at `address`, playing `host_player`.
+    pub fn host(address: impl ToSocketAddrs, host_player: State) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            role: Role::Host(listener),
+            host_player,
+            peer: None,
+            pending: None,
+            greeted: false,
+            shared: None,
+        })
+    }
+
+    /// Joins the host at `address`, which picks who plays what.
+    pub fn join(address: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address to join")
+        })?;
+        let mut session = Self {
+            role: Role::Join(address),
+            host_player: State::Circle,
+            peer: None,
+            pending: None,
+            greeted: false,
+            shared: None,
+        };
+        session.reconnect()?;
+        Ok(session)
+    }
+
+    /// Connects to the host again after the connection was lost, keeping the game.
+    pub fn reconnect(&mut self) -> std::io::Result<()> {
+        let Role::Join(address) = self.role else {
+            return Ok(());
+        };
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        self.peer = Some(Peer::new(stream)?);
+        self.greeted = false;
+        Ok(())
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host(_))
+    }
+
+    /// The address a host listens on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.role {
+            Role::Host(listener) => listener.local_addr().ok(),
+            Role::Join(_) => None,
+        }
+    }
+
+    /// Whether the game is being played with the other side right now.
+    pub fn is_connected(&self) -> bool {
+        self.peer.is_some() && self.greeted && self.shared.is_some()
+    }
+
+    /// Whether this side plays `player`.
+    pub fn plays(&self, player: State) -> bool {
+        (player == self.host_player) == self.is_host()
+    }
+
+    /// Whether this side can move in `game` now.
+    pub fn is_local_turn(&self, game: &Game) -> bool {
+        self.is_connected() && game.outcome().is_none() && self.plays(game.current_player())
+    }
+
+    /// Accepts joiners, plays the moves that came in on `game` and sends the changes to `game`
+    /// since the last update.
+    pub fn update(&mut self, game: &mut Game) -> Vec<NetEvent> {
+        let mut events = vec![];
+        if let Err(error) = self.try_update(game, &mut events) {
+            self.peer = None;
+            events.push(match error {
+                NetError::Disconnected => NetEvent::Disconnected,
+                error => NetEvent::Error(error),
+            });
+        }
+        events
+    }
+
+    fn try_update(&mut self, game: &mut Game, events: &mut Vec<NetEvent>) -> Result<(), NetError> {
+        if let Role::Host(listener) = &self.role {
+            match listener.accept() {
+                Ok((stream, _)) if self.peer.is_none() => {
+                    self.peer = Some(Peer::new(stream)?);
+                    self.greeted = false;
+                }
+                // a joiner that comes back replaces a connection that may not have noticed yet
+                // that it is gone, but anyone else that connects must not end the game
+                Ok((stream, _)) => self.pending = Peer::new(stream).ok(),
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        self.poll_pending(game, events)?;
+
+        while let Some(message) = self.peer.as_ref().and_then(Peer::poll) {
+            self.receive(message?, game, events)?;
+        }
+
+        if self.peer.is_some() && self.greeted {
+            self.send_changes(game, events)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the peer with the pending joiner once that said hello, or drops the joiner if it
+    /// said anything else.
+    fn poll_pending(
+        &mut self,
+        game: &mut Game,
+        events: &mut Vec<NetEvent>,
+    ) -> Result<(), NetError> {
+        let Some(message) = self.pending.as_ref().and_then(Peer::poll) else {
+            return Ok(());
+        };
+        match message {
+            Ok(Message::Hello { version }) if version == Message::VERSION => {
+                self.peer = self.pending.take();
+                self.greeted = false;
+                self.receive(Message::Hello { version }, game, events)
+            }
+            _ => {
+                self.pending = None;
+                Ok(())
+            }
+        }
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), NetError> {
+        match &mut self.peer {
+            Some(peer) => Ok(peer.send(message)?),
+            None => Err(NetError::Disconnected),
+        }
+    }
+
+    fn send_game(&mut self, game: &Game) -> Result<(), NetError> {
+        let save_file = SaveFile::from_game(game);
+        self.send(&Message::Game {
+            host_player: self.host_player,
+            save_file: Box::new(save_file.clone()),
+        })?;
+        self.shared = Some(save_file);
+        Ok(())
+    }
+
+    fn receive(
+        &mut self,
+        message: Message,
+        game: &mut Game,
+        events: &mut Vec<NetEvent>,
+    ) -> Result<(), NetError> {
+        match message {
+            Message::Hello { version } if version != Message::VERSION => {
+                return Err(NetError::UnsupportedVersion(version))
+            }
+            Message::Hello { .. } => {
+                self.greeted = true;
+                if self.is_host() {
+                    self.send_game(game)?;
+                    events.push(NetEvent::Connected);
+                }
+            }
+            _ if !self.greeted => {
+                return Err(NetError::InvalidMessage("expected hello".to_string()))
+            }
+            Message::Game {
+                host_player,
+                save_file,
+            } if !self.is_host() => {
+                if !save_file.shape.fits(save_file.num_layers) {
+                    return Err(NetError::InvalidGame(LoadError::TooLarge));
+                }
+                let new_game = save_file.to_game().map_err(NetError::InvalidGame)?;
+                if self.shared.is_none() {
+                    events.push(NetEvent::Connected);
+                }
+                self.host_player = host_player;
+                self.shared = Some(SaveFile::from_game(&new_game));
+                *game = new_game.clone();
+                events.push(NetEvent::NewGame(Box::new(new_game)));
+            }
+            Message::Resync if self.is_host() => self.send_game(game)?,
+            Message::Move { mv, hash } => {
+                let Some(shared) = &self.shared else {
+                    // the joiner asked for the game again, which will have this move
+                    return Ok(());
+                };
+                if SaveFile::from_game(game) != *shared {
+                    // a move of our own that was not sent yet can't be before theirs, so one of
+                    // the boards is off
+                    return self.desync(game, events);
+                }
+                let player = game.current_player();
+                if self.plays(player) {
+                    return Err(NetError::NotTheirTurn(player));
+                }
+                game.play(&mv)
+                    .map_err(|error| NetError::IllegalMove(mv.clone(), error))?;
+                if board_hash(game.board()) != hash {
+                    game.undo();
+                    return self.desync(game, events);
+                }
+                self.shared = Some(SaveFile::from_game(game));
+            }
+            message => return Err(NetError::InvalidMessage(format!("unexpected {message:?}"))),
+        }
+        Ok(())
+    }
+
+    fn desync(&mut self, game: &Game, events: &mut Vec<NetEvent>) -> Result<(), NetError> {
+        events.push(NetEvent::Desync);
+        if self.is_host() {
+            self.send_game(game)
+        } else {
+            self.send(&Message::Resync)
+        }
+    }
+
+    /// Sends the moves that were played on `game` since it was last shared, or the whole game if
+    /// it changed in any other way.
+    fn send_changes(&mut self, game: &Game, events: &mut Vec<NetEvent>) -> Result<(), NetError> {
+        let Some(shared) = &self.shared else {
+            return Ok(());
+        };
+        let current = SaveFile::from_game(game);
+        if current == *shared {
+            return Ok(());
+        }
+
+        let same_start = SaveFile {
+            moves: vec![],
+            turn: None,
+            ..current.clone()
+        } == SaveFile {
+            moves: vec![],
+            turn: None,
+            ..shared.clone()
+        };
+        let new_moves = current.moves.strip_prefix(&shared.moves[..]);
+        match new_moves {
+            Some(new_moves) if same_start => {
+                let mut replay = game.clone();
+                for _ in new_moves {
+                    replay.undo();
+                }
+                for mv in new_moves {
+                    replay.play(mv).unwrap();
+                    let hash = board_hash(replay.board());
+                    self.send(&Message::Move {
+                        mv: mv.clone(),
+                        hash,
+                    })?;
+                }
+                self.shared = Some(current);
+                Ok(())
+            }
+            _ if self.is_host() => self.send_game(game),
+            _ => {
+                // only the host changes the game, so get it back
+                self.shared = None;
+                self.send(&Message::Resync)?;
+                events.push(NetEvent::Desync);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Updates both sides until `done` or a few seconds passed.
+    fn exchange(
+        host: &mut NetSession,
+        host_game: &mut Game,
+        joiner: &mut NetSession,
+        joiner_game: &mut Game,
+        done: impl Fn(&Game, &NetSession, &Game) -> bool,
+    ) {
+        let start = std::time::Instant::now();
+        while !done(host_game, joiner, joiner_game) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            for event in host.update(host_game) {
+                assert!(!matches!(event, NetEvent::Error(_)), "{event}");
+            }
+            for event in joiner.update(joiner_game) {
+                assert!(!matches!(event, NetEvent::Error(_)), "{event}");
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn same_moves(a: &Game, b: &Game) -> bool {
+        a.moves().eq(b.moves()) && board_hash(a.board()) == board_hash(b.board())
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let mut game = Game::new(2);
+        game.play(&"5.7".parse().unwrap()).unwrap();
+        let messages = [
+            Message::Hello {
+                version: Message::VERSION,
+            },
+            Message::Game {
+                host_player: State::Cross,
+                save_file: Box::new(SaveFile::from_game(&game)),
+            },
+            Message::Move {
+                mv: "7.5".parse().unwrap(),
+                hash: board_hash(game.board()),
+            },
+            Message::Resync,
+        ];
+        let mut bytes = vec![];
+        for message in &messages {
+            message.write_to(&mut bytes).unwrap();
+        }
+        let mut reader = &bytes[..];
+        for message in messages {
+            assert_eq!(Message::read_from(&mut reader).unwrap(), Some(message));
+        }
+        assert_eq!(Message::read_from(&mut reader).unwrap(), None);
+        assert!(matches!(
+            Message::read_from(&mut &b"move 5.7\n"[..]),
+            Err(NetError::InvalidMessage(_))
+        ));
+        let mut long_line = b"game Circle 1\nmoves ".to_vec();
+        long_line.resize(MAX_LINE_LEN as usize * 2, b'5');
+        assert!(matches!(
+            Message::read_from(&mut &long_line[..]),
+            Err(NetError::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn play_and_rejoin() {
+        let mut host = NetSession::host("127.0.0.1:0", State::Circle).unwrap();
+        let address = host.local_addr().unwrap();
+        let mut host_game = Game::new(2);
+        host_game.play(&"5.7".parse().unwrap()).unwrap();
+
+        let mut joiner = NetSession::join(address).unwrap();
+        let mut joiner_game = Game::new(1);
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |_, _, joiner_game| joiner_game.num_moves() == 1,
+        );
+        assert!(joiner.is_local_turn(&joiner_game));
+        assert!(!host.is_local_turn(&host_game));
+
+        joiner_game.play(&"7.5".parse().unwrap()).unwrap();
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |host_game, _, _| host_game.num_moves() == 2,
+        );
+        host_game.play(&"5.8".parse().unwrap()).unwrap();
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |host_game, _, joiner_game| same_moves(host_game, joiner_game),
+        );
+
+        // the joiner comes back with a new connection and gets the game again
+        drop(joiner);
+        let mut joiner = NetSession::join(address).unwrap();
+        let mut joiner_game = Game::new(1);
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |host_game, _, joiner_game| same_moves(host_game, joiner_game),
+        );
+        assert_eq!(joiner_game.num_moves(), 3);
+        assert!(joiner.is_local_turn(&joiner_game));
+    }
+
+    #[test]
+    fn undo_on_the_host_is_synced() {
+        let mut host = NetSession::host("127.0.0.1:0", State::Cross).unwrap();
+        let mut host_game = Game::new(1);
+        let mut joiner = NetSession::join(host.local_addr().unwrap()).unwrap();
+        let mut joiner_game = Game::new(1);
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |_, joiner, _| joiner.is_connected(),
+        );
+
+        joiner_game.play(&"5".parse().unwrap()).unwrap();
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |host_game, _, _| host_game.num_moves() == 1,
+        );
+        host_game.undo();
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |_, _, joiner_game| joiner_game.num_moves() == 0,
+        );
+
+        // the joiner can't take moves back on its own
+        joiner_game.play(&"1".parse().unwrap()).unwrap();
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |host_game, _, _| host_game.num_moves() == 1,
+        );
+        joiner_game.undo();
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |_, _, joiner_game| joiner_game.num_moves() == 1,
+        );
+    }
+
+    #[test]
+    fn others_connecting_keep_the_game() {
+        let mut host = NetSession::host("127.0.0.1:0", State::Cross).unwrap();
+        let mut host_game = Game::new(1);
+        let mut joiner = NetSession::join(host.local_addr().unwrap()).unwrap();
+        let mut joiner_game = Game::new(1);
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |_, joiner, _| joiner.is_connected(),
+        );
+
+        let mut stranger = TcpStream::connect(host.local_addr().unwrap()).unwrap();
+        let start = std::time::Instant::now();
+        while host.pending.is_none() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            assert!(host.update(&mut host_game).is_empty());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        stranger.write_all(b"hello 99\n").unwrap();
+        while host.pending.is_some() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            assert!(host.update(&mut host_game).is_empty());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(host.is_connected());
+
+        joiner_game.play(&"5".parse().unwrap()).unwrap();
+        exchange(
+            &mut host,
+            &mut host_game,
+            &mut joiner,
+            &mut joiner_game,
+            |host_game, _, _| host_game.num_moves() == 1,
+        );
+    }
+}
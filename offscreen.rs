@@ -0,0 +1,434 @@
+use std::num::NonZeroU32;
+
+use eframe::wgpu;
+
+use crate::{
+    render_board, Board, Camera, CellPath, PerObjectData, PlayerStyle, RenderState, State,
+};
+
+/// The color around and between the boards, like the dark theme of the window.
+const BACKGROUND: wgpu::Color = wgpu::Color {
+    r: 0.02,
+    g: 0.02,
+    b: 0.02,
+    a: 1.0,
+};
+
+#[derive(Debug)]
+pub enum OffscreenError {
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The image is empty or bigger than the device can draw, which is at most `max` pixels
+    /// along each side.
+    InvalidSize {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    Io(std::io::Error),
+    Png(png::EncodingError),
+}
+
+impl std::fmt::Display for OffscreenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OffscreenError::NoAdapter => write!(f, "no graphics adapter was found"),
+            OffscreenError::RequestDevice(error) => write!(f, "{error}"),
+            OffscreenError::InvalidSize { width, height, max } => write!(
+                f,
+                "an image of {width} by {height} pixels can't be drawn, it has to be 1 to {max} \
+                 pixels along each side"
+            ),
+            OffscreenError::Io(error) => write!(f, "{error}"),
+            OffscreenError::Png(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for OffscreenError {}
+
+impl From<std::io::Error> for OffscreenError {
+    fn from(error: std::io::Error) -> Self {
+        OffscreenError::Io(error)
+    }
+}
+
+impl From<png::EncodingError> for OffscreenError {
+    fn from(error: png::EncodingError) -> Self {
+        OffscreenError::Png(error)
+    }
+}
+
+/// An image as rows of RGBA pixels from the top down, 4 bytes each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn write_png(&self, writer: impl std::io::Write) -> Result<(), OffscreenError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> Result<(), OffscreenError> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file))
+    }
+}
+
+/// Draws boards with the same pipeline as the window of the [`App`](crate::App), but into a
+/// texture that is read back, so no window or display is needed.
+///
+/// Without a GPU wgpu can fall back to a software adapter like lavapipe or llvmpipe, which is
+/// what CI machines have.
+pub struct OffscreenRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    render_state: RenderState,
+    /// Counts the renders, every render uploads its own objects.
+    version: u64,
+}
+
+impl OffscreenRenderer {
+    /// The shader writes its colors as they are, so the pixels are exactly what it computed.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    /// Sets up rendering on the first adapter that works, or only on a software adapter if
+    /// `software` is set, which gives the same pixels on every machine. `sample_count` is 1, or 4
+    /// for multisampling.
+    pub fn new(software: bool, sample_count: u32) -> Result<Self, OffscreenError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: software,
+            compatible_surface: None,
+        }))
+        .ok_or(OffscreenError::NoAdapter)?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("Offscreen Device"),
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            },
+            None,
+        ))
+        .map_err(OffscreenError::RequestDevice)?;
+        let render_state = RenderState::new(&device, Self::FORMAT, sample_count);
+        Ok(Self {
+            device,
+            queue,
+            render_state,
+            version: 0,
+        })
+    }
+
+    /// Draws the objects as `camera` sees them into an image of `width` by `height` pixels.
+    pub fn render(
+        &mut self,
+        mut camera: Camera,
+        data: &[PerObjectData],
+        width: u32,
+        height: u32,
+    ) -> Result<Image, OffscreenError> {
+        // rows of a copy have to start at multiples of the alignment
+        let row_size = u64::from(width) * 4;
+        let alignment = u64::from(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let padded_row_size = row_size.div_ceil(alignment) * alignment;
+        let buffer_size = padded_row_size * u64::from(height);
+        let limits = self.device.limits();
+        let max = limits.max_texture_dimension_2d;
+        if !(1..=max).contains(&width)
+            || !(1..=max).contains(&height)
+            || buffer_size > limits.max_buffer_size
+        {
+            return Err(OffscreenError::InvalidSize { width, height, max });
+        }
+
+        camera.screen_size = (width as f32, height as f32).into();
+        let create_texture = |label, sample_count, usage| {
+            self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::FORMAT,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let texture = create_texture(
+            "Offscreen Texture",
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // with multisampling the samples are drawn elsewhere and resolved into the texture
+        let sample_count = self.render_state.sample_count();
+        let multisampled_view = (sample_count > 1).then(|| {
+            create_texture(
+                "Offscreen Multisampled Texture",
+                sample_count,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Read Buffer"),
+            size: buffer_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+        self.version += 1;
+        self.render_state.prepare(
+            camera,
+            data,
+            self.version,
+            &self.device,
+            &self.queue,
+            &mut encoder,
+        );
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Offscreen Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: multisampled_view.as_ref().unwrap_or(&view),
+                    resolve_target: multisampled_view.as_ref().map(|_| &view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(BACKGROUND),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.render_state.render(&mut render_pass);
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    // fits, since rows are at most as long as the biggest texture
+                    bytes_per_row: NonZeroU32::new(padded_row_size as u32),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("the read buffer can be mapped")
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        let pixels = slice
+            .get_mapped_range()
+            .chunks(padded_row_size as usize)
+            .flat_map(|row| &row[..row_size as usize])
+            .copied()
+            .collect();
+        buffer.unmap();
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Draws the whole of `board` like the window does, with the board at `target_board`
+    /// highlighted, fitted into an image of `width` by `height` pixels.
+    pub fn render_board(
+        &mut self,
+        board: &Board,
+        target_board: &CellPath,
+        styles: &[PlayerStyle; 4],
+        width: u32,
+        height: u32,
+    ) -> Result<Image, OffscreenError> {
+        let camera = Camera::fit(board, width as f32, height as f32);
+        let mut data = vec![];
+        render_board(board, target_board, styles, Some(&camera), &mut data);
+        self.render(camera, &data, width, height)
+    }
+}
+
+impl Camera {
+    /// A camera that shows all of `board` on a screen of this size, with a small margin.
+    pub fn fit(board: &Board, width: f32, height: f32) -> Self {
+        let aspect = width / height;
+        Self {
+            position: (0.0, 0.0).into(),
+            screen_size: (width, height).into(),
+            rotation: 0.0,
+            scale: 1.9 / board.shape().size as f32 * aspect.min(1.0),
+        }
+    }
+}
+
+/// The default look of every player, for pictures that don't come from the window.
+pub fn default_styles() -> [PlayerStyle; 4] {
+    State::ALL.map(PlayerStyle::default_for)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Game, Glyph};
+
+    /// Compares `image` with the golden image of `name` in `src/goldens/`, allowing for the small
+    /// differences between rasterizers. The goldens are only written when `UPDATE_GOLDENS` is
+    /// set, so a missing one fails the test.
+    fn check_golden(name: &str, image: &Image) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/goldens")
+            .join(format!("{name}.png"));
+        if std::env::var_os("UPDATE_GOLDENS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image.save_png(&path).unwrap();
+            return;
+        }
+        assert!(
+            path.exists(),
+            "{} is missing, run with UPDATE_GOLDENS=1 to write it",
+            path.display()
+        );
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (image.width, image.height));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+
+        let different = pixels
+            .chunks(4)
+            .zip(image.pixels.chunks(4))
+            .filter(|(golden, pixel)| golden.iter().zip(*pixel).any(|(a, b)| a.abs_diff(*b) > 8))
+            .count();
+        // edges of shapes may land on either side of a pixel center
+        let allowed = (image.width * image.height / 200) as usize;
+        assert!(
+            different <= allowed,
+            "{different} pixels differ from {}",
+            path.display()
+        );
+    }
+
+    /// The renderer for the tests. They need an adapter, a software one will do, so they are
+    /// ignored unless run with `cargo test -- --ignored`.
+    fn renderer(sample_count: u32) -> OffscreenRenderer {
+        OffscreenRenderer::new(true, sample_count).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    #[test]
+    #[ignore = "needs an adapter"]
+    fn golden_boards() {
+        let mut renderer = renderer(1);
+        let mut game = Game::new(2);
+        for mv in ["5.7", "7.5", "5.8", "8.5", "5.9", "9.1"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        let image = renderer
+            .render_board(
+                game.board(),
+                &game.target_board(),
+                &default_styles(),
+                256,
+                256,
+            )
+            .unwrap();
+        check_golden("two_layers", &image);
+
+        // every glyph, on a wide image
+        let mut game = Game::new(1);
+        game.set_rules("players=4".parse().unwrap());
+        for mv in ["1", "2", "3", "4", "5"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        let image = renderer
+            .render_board(
+                game.board(),
+                &game.target_board(),
+                &default_styles(),
+                320,
+                200,
+            )
+            .unwrap();
+        check_golden("glyphs", &image);
+    }
+
+    #[test]
+    #[ignore = "needs an adapter"]
+    fn empty_scene_is_background() {
+        let mut renderer = renderer(1);
+        let camera = Camera::fit(&Board::default(), 16.0, 8.0);
+        let image = renderer.render(camera, &[], 16, 8).unwrap();
+        assert_eq!(image.pixels.len(), 16 * 8 * 4);
+        let background = [5, 5, 5, 255];
+        assert!(image.pixels.chunks(4).all(|pixel| pixel == background));
+
+        for (width, height) in [(0, 8), (70_000, 8), (16, u32::MAX)] {
+            assert!(matches!(
+                renderer.render(camera, &[], width, height),
+                Err(OffscreenError::InvalidSize { .. })
+            ));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs an adapter"]
+    fn edges_fade_into_the_background() {
+        let ring = PerObjectData {
+            object_position: (0.0, 0.0).into(),
+            rotation: 0.0,
+            scale: (1.5, 1.5).into(),
+            color: (1.0, 1.0, 1.0).into(),
+            glyph: Glyph::Ring as u32,
+            line_width: 0.1,
+        };
+        let camera = Camera::fit(&Board::default(), 64.0, 64.0);
+        for sample_count in [1, 4] {
+            let mut renderer = renderer(sample_count);
+            let image = renderer.render(camera, &[ring], 64, 64).unwrap();
+            let edges = image
+                .pixels
+                .chunks(4)
+                .filter(|pixel| (20..240).contains(&pixel[0]))
+                .count();
+            // a ring 30 pixels wide has almost 200 pixels on its edges
+            assert!(edges > 100, "{edges} pixels between ring and background");
+            assert!(image.pixels.chunks(4).all(|pixel| pixel[3] == 255));
+        }
+    }
+}
@@ -8,8 +8,23 @@ pub struct PerObjectData {
     pub rotation: f32,
     pub scale: cgmath::Vector2<f32>,
     pub color: cgmath::Vector3<f32>,
-    pub is_circle: u32,
-    pub circle_width: f32,
+    /// A [`Glyph`] as a number.
+    pub glyph: u32,
+    /// The width of the outline of glyphs other than [`Glyph::Filled`], in texture coordinates.
+    pub line_width: f32,
+}
+
+/// The shape the shader cuts out of an object's quad.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    /// The whole quad, for grid lines, highlights and the bars of a cross.
+    Filled = 0,
+    Ring = 1,
+    /// The outline of a triangle pointing up.
+    Triangle = 2,
+    /// The outline of a square.
+    Square = 3,
 }
 
 // cgmath::Vector2 doesnt implement these traits, but i know its valid
@@ -39,12 +54,12 @@ impl PerObjectData {
             format: wgpu::VertexFormat::Float32x3,
         },
         wgpu::VertexAttribute {
-            offset: offset_of!(Self, is_circle) as wgpu::BufferAddress,
+            offset: offset_of!(Self, glyph) as wgpu::BufferAddress,
             shader_location: 4,
             format: wgpu::VertexFormat::Uint32,
         },
         wgpu::VertexAttribute {
-            offset: offset_of!(Self, circle_width) as wgpu::BufferAddress,
+            offset: offset_of!(Self, line_width) as wgpu::BufferAddress,
             shader_location: 5,
             format: wgpu::VertexFormat::Float32,
         },
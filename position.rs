@@ -0,0 +1,425 @@
+use crate::{Board, BoardShape, CellPath, Element, Rules, State};
+
+/// Everything needed to continue a game, written on one line like chess's FEN.
+///
+/// ```text
+/// (3/1x1/3)(3/3/3)(3/3/x2)/(3/3/3)(o2/1o1/2o)(3/3/3)/(3/3/3)(3/3/3)(3/3/3) x 8
+/// ```
+///
+/// The board comes first, then the player to move and last the board that has to be played in as
+/// a [`CellPath`], `-` for a free choice. Players are written as `o` for Circle, `x` for Cross,
+/// `t` for Triangle and `s` for Square. A board lists its rows from the top down, separated by
+/// `/`, and every row lists its cells from left to right: a player for played cells, a digit for
+/// that many empty cells and a sub-board in parentheses, written the same way. Sub-boards that
+/// have a winner are read as won.
+///
+/// Boards can have any number of rows, as long as every board has as many columns as rows. A
+/// full row wins unless the number of cells in a row needed to win is given as a fourth field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub board: Board,
+    pub turn: State,
+    pub target: CellPath,
+}
+
+impl Position {
+    /// The number of nested boards, which is the same everywhere on the board.
+    pub fn num_layers(&self) -> usize {
+        self.board
+            .num_layers()
+            .expect("positions have the same number of layers everywhere")
+    }
+
+    /// Checks that the player to move takes part in a game with `rules`, which the position
+    /// doesn't say by itself.
+    pub fn check_rules(&self, rules: Rules) -> Result<(), ParsePositionError> {
+        if rules.players().contains(&self.turn) {
+            Ok(())
+        } else {
+            Err(ParsePositionError::InvalidTurn(
+                state_to_char(self.turn).to_string(),
+            ))
+        }
+    }
+}
+
+fn state_to_char(state: State) -> char {
+    match state {
+        State::Circle => 'o',
+        State::Cross => 'x',
+        State::Triangle => 't',
+        State::Square => 's',
+    }
+}
+
+fn state_from_char(c: char) -> Option<State> {
+    match c {
+        'o' => Some(State::Circle),
+        'x' => Some(State::Cross),
+        't' => Some(State::Triangle),
+        's' => Some(State::Square),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = self.shape().size;
+        for y in (0..size).rev() {
+            if y + 1 < size {
+                write!(f, "/")?;
+            }
+            let mut empty = 0;
+            for x in 0..size {
+                if let Element::State(None) = &self.elements()[x][y] {
+                    empty += 1;
+                    if empty == 9 {
+                        write!(f, "{empty}")?;
+                        empty = 0;
+                    }
+                    continue;
+                }
+                if empty > 0 {
+                    write!(f, "{empty}")?;
+                    empty = 0;
+                }
+                match &self.elements()[x][y] {
+                    Element::State(None) => unreachable!(),
+                    &Element::State(Some(state)) => write!(f, "{}", state_to_char(state))?,
+                    Element::Board(board) | Element::Won(_, board) => write!(f, "({board})")?,
+                }
+            }
+            if empty > 0 {
+                write!(f, "{empty}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.board,
+            state_to_char(self.turn),
+            self.target.display(self.board.shape().size)
+        )?;
+        let shape = self.board.shape();
+        if shape.win_length != shape.size {
+            write!(f, " {}", shape.win_length)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePositionError {
+    /// The board is malformed, the number is the offset of the first character that could not
+    /// be read.
+    InvalidBoard(usize),
+    /// Some parts of the board have more layers than others.
+    UnevenLayers,
+    /// Some boards have more rows and columns than others.
+    UnevenSizes,
+    /// The boards are nested deeper than [`BoardShape::MAX_LAYERS`].
+    TooManyLayers,
+    InvalidTurn(String),
+    /// The target is not a path to a board.
+    InvalidTarget(String),
+    /// The number of cells in a row needed to win is not a number from 1 to the board size.
+    InvalidWinLength(String),
+    MissingField,
+}
+
+impl std::fmt::Display for ParsePositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsePositionError::InvalidBoard(offset) => {
+                write!(f, "the board is invalid at character {}", offset + 1)
+            }
+            ParsePositionError::UnevenLayers => {
+                write!(
+                    f,
+                    "the board does not have the same number of layers everywhere"
+                )
+            }
+            ParsePositionError::UnevenSizes => {
+                write!(f, "the boards do not all have the same size")
+            }
+            ParsePositionError::TooManyLayers => {
+                write!(
+                    f,
+                    "the board has more than {} layers",
+                    BoardShape::MAX_LAYERS
+                )
+            }
+            ParsePositionError::InvalidTurn(turn) => write!(f, "unknown player `{turn}`"),
+            ParsePositionError::InvalidTarget(target) => {
+                write!(f, "`{target}` is not a board to play in")
+            }
+            ParsePositionError::InvalidWinLength(win_length) => {
+                write!(f, "`{win_length}` is not a number of cells in a row to win")
+            }
+            ParsePositionError::MissingField => {
+                write!(f, "a position needs a board, a player and a target")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParsePositionError {}
+
+/// Reads a board on layer `layer`, counting from 1 at the top, and its sub-boards, where a line of
+/// `win_length` cells wins, or a full row if it is `None`.
+fn parse_board(
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+    end: usize,
+    win_length: Option<usize>,
+    layer: usize,
+) -> Result<Board, ParsePositionError> {
+    // the rows from the top down, the first row sets the size of the board
+    let mut rows: Vec<Vec<Element>> = vec![vec![]];
+    let fits = |rows: &[Vec<Element>], cells: usize| {
+        rows.len() == 1 || rows.last().unwrap().len() + cells <= rows[0].len()
+    };
+    let end = loop {
+        let Some(&(i, c)) = chars.peek() else {
+            break end;
+        };
+        match c {
+            ')' => break i,
+            '/' => {
+                let size = rows[0].len();
+                if rows.len() > 1 && (rows.len() == size || rows.last().unwrap().len() != size) {
+                    return Err(ParsePositionError::InvalidBoard(i));
+                }
+                rows.push(vec![]);
+            }
+            '1'..='9' => {
+                let empty = c.to_digit(10).unwrap() as usize;
+                if !fits(&rows, empty) {
+                    return Err(ParsePositionError::InvalidBoard(i));
+                }
+                let row = rows.last_mut().unwrap();
+                row.extend(std::iter::repeat_with(Element::default).take(empty));
+            }
+            '(' => {
+                if !fits(&rows, 1) {
+                    return Err(ParsePositionError::InvalidBoard(i));
+                }
+                if layer == BoardShape::MAX_LAYERS {
+                    return Err(ParsePositionError::TooManyLayers);
+                }
+                chars.next();
+                let sub_board = parse_board(chars, end, win_length, layer + 1)?;
+                match chars.peek() {
+                    Some((_, ')')) => {}
+                    Some(&(i, _)) => return Err(ParsePositionError::InvalidBoard(i)),
+                    None => return Err(ParsePositionError::InvalidBoard(end)),
+                }
+                rows.last_mut().unwrap().push(match sub_board.get_winner() {
+                    Some(winner) => Element::Won(winner, Box::new(sub_board)),
+                    None => Element::Board(Box::new(sub_board)),
+                });
+            }
+            _ => {
+                let state = state_from_char(c)
+                    .filter(|_| fits(&rows, 1))
+                    .ok_or(ParsePositionError::InvalidBoard(i))?;
+                rows.last_mut().unwrap().push(Element::State(Some(state)));
+            }
+        }
+        chars.next();
+    };
+
+    let size = rows.len();
+    if rows.iter().any(|row| row.len() != size) {
+        return Err(ParsePositionError::InvalidBoard(end));
+    }
+    let mut board = Board::new(BoardShape {
+        size,
+        win_length: win_length.unwrap_or(size),
+    });
+    for (row, y) in rows.into_iter().zip((0..size).rev()) {
+        for (x, element) in row.into_iter().enumerate() {
+            if element != Element::default() {
+                board.set(&[(x, y)], element);
+            }
+        }
+    }
+    Ok(board)
+}
+
+/// Whether every board in `board` has `shape`.
+fn has_shape(board: &Board, shape: BoardShape) -> bool {
+    board.shape() == shape
+        && board
+            .elements()
+            .iter()
+            .flatten()
+            .all(|element| match element {
+                Element::State(_) => true,
+                Element::Board(sub_board) | Element::Won(_, sub_board) => {
+                    has_shape(sub_board, shape)
+                }
+            })
+}
+
+fn parse_whole_board(s: &str, win_length: Option<usize>) -> Result<Board, ParsePositionError> {
+    let mut chars = s.char_indices().peekable();
+    let board = parse_board(&mut chars, s.len(), win_length, 1)?;
+    if let Some((i, _)) = chars.next() {
+        return Err(ParsePositionError::InvalidBoard(i));
+    }
+    board.num_layers().ok_or(ParsePositionError::UnevenLayers)?;
+    if !has_shape(&board, board.shape()) {
+        return Err(ParsePositionError::UnevenSizes);
+    }
+    Ok(board)
+}
+
+/// Reads a board where a full row wins, use [`Position`] for other win lengths.
+impl std::str::FromStr for Board {
+    type Err = ParsePositionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_whole_board(s, None)
+    }
+}
+
+impl std::str::FromStr for Position {
+    type Err = ParsePositionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (board, turn, target, win_length) = match *s.split_whitespace().collect::<Vec<_>>() {
+            [board, turn, target] => (board, turn, target, None),
+            [board, turn, target, win_length] => (board, turn, target, Some(win_length)),
+            _ => return Err(ParsePositionError::MissingField),
+        };
+        let win_length = win_length
+            .map(|win_length| {
+                win_length
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&win_length| win_length > 0)
+                    .ok_or_else(|| ParsePositionError::InvalidWinLength(win_length.to_string()))
+            })
+            .transpose()?;
+        let board = parse_whole_board(board, win_length)?;
+        if let Some(win_length) = win_length {
+            if win_length > board.shape().size {
+                return Err(ParsePositionError::InvalidWinLength(win_length.to_string()));
+            }
+        }
+
+        let mut chars = turn.chars();
+        let turn = match (chars.next().and_then(state_from_char), chars.next()) {
+            (Some(state), None) => state,
+            _ => return Err(ParsePositionError::InvalidTurn(turn.to_string())),
+        };
+
+        let invalid_target = || ParsePositionError::InvalidTarget(target.to_string());
+        let target =
+            CellPath::parse_sized(target, board.shape().size).map_err(|_| invalid_target())?;
+        target.resolve_board(&board).ok_or_else(invalid_target)?;
+
+        Ok(Self {
+            board,
+            turn,
+            target,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_and_parsing() {
+        let text = "(3/1x1/3)(3/3/3)(3/3/x2)/(3/3/3)(o2/1o1/2o)(3/3/3)/(3/3/3)(3/3/3)(3/3/3) x 8";
+        let position: Position = text.parse().unwrap();
+        assert_eq!(position.to_string(), text);
+        assert_eq!(position.num_layers(), 2);
+        assert_eq!(position.turn, State::Cross);
+        assert_eq!(position.target, "8".parse().unwrap());
+        assert!(matches!(
+            position.board.elements()[1][1],
+            Element::Won(State::Circle, _)
+        ));
+        assert!(matches!(
+            "b2/c1"
+                .parse::<CellPath>()
+                .unwrap()
+                .resolve(&position.board),
+            Some(Element::State(Some(State::Circle)))
+        ));
+
+        let position: Position = "x2/1o1/3 o -".parse().unwrap();
+        assert_eq!(position.num_layers(), 1);
+        assert_eq!(position.target, CellPath::new());
+    }
+
+    #[test]
+    fn other_sizes() {
+        let text = "x3/4/1o2/4 o - 3";
+        let position: Position = text.parse().unwrap();
+        assert_eq!(position.to_string(), text);
+        assert_eq!(
+            position.board.shape(),
+            BoardShape {
+                size: 4,
+                win_length: 3
+            }
+        );
+        assert_eq!(
+            position.board.elements()[1][1],
+            Element::State(Some(State::Circle))
+        );
+
+        let board: Board = "x1/2".parse().unwrap();
+        assert_eq!(board.shape().win_length, 2);
+        assert_eq!(
+            "4/4/4/4 o - 5".parse::<Position>().err(),
+            Some(ParsePositionError::InvalidWinLength("5".to_string()))
+        );
+        assert_eq!(
+            "(2/2)(2/2)/(2/2)(3/3/3)".parse::<Board>().err(),
+            Some(ParsePositionError::UnevenSizes)
+        );
+    }
+
+    #[test]
+    fn invalid_positions() {
+        assert_eq!(
+            "3/3/3 o".parse::<Position>().err(),
+            Some(ParsePositionError::MissingField)
+        );
+        assert_eq!(
+            "3/4/3".parse::<Board>().err(),
+            Some(ParsePositionError::InvalidBoard(2))
+        );
+        assert_eq!(
+            "3/3/3/".parse::<Board>().err(),
+            Some(ParsePositionError::InvalidBoard(5))
+        );
+        assert_eq!(
+            "(3/3/3)2/3/3".parse::<Board>().err(),
+            Some(ParsePositionError::UnevenLayers)
+        );
+        assert_eq!(
+            "(".repeat(100_000).parse::<Board>().err(),
+            Some(ParsePositionError::TooManyLayers)
+        );
+        assert_eq!(
+            "3/3/3 o 5".parse::<Position>().err(),
+            Some(ParsePositionError::InvalidTarget("5".to_string()))
+        );
+        assert_eq!(
+            "3/3/3 Circle -".parse::<Position>().err(),
+            Some(ParsePositionError::InvalidTurn("Circle".to_string()))
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    BoardShape, CellPath, Game, Move, MoveError, Outcome, ParseCellPathError, ParsePositionError,
+    ParseRulesError, Position, Rules, State,
+};
+
+/// A game record for sharing games, modeled after chess's PGN.
+///
+/// A record starts with `[Name "Value"]` tags, followed by the moves numbered in pairs and the
+/// result:
+///
+/// ```text
+/// [Layers "2"]
+/// [Circle "Alice"]
+/// [Cross "Bob"]
+/// [Result "1-0"]
+/// [Date "2023.04.01"]
+///
+/// 1. b2/a1 a1/b2 2. b2/b1 b1/b2 3. b2/c1 1-0
+/// ```
+///
+/// Every move names the cell at each layer with a column letter and a row number, see
+/// [`CellPath`](crate::CellPath). The result is `1-0` when Circle, who moves first, won, `0-1`
+/// when Cross won, `0-0-1` and `0-0-0-1` when Triangle or Square won in games with more players,
+/// `1/2-1/2` for a stalemate and `*` for a game that is not finished.
+/// Comments in `{}` are skipped. Games that were set up from a [`Position`](crate::Position)
+/// have a `Position` tag that the moves start from. Games on boards other than 3 by 3 with 3 in a
+/// row to win have `Size` and `WinLength` tags, and games with other [`Rules`] have a `Rules` tag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameRecord {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<Move>,
+}
+
+impl GameRecord {
+    pub fn from_game(game: &Game) -> Self {
+        let mut record = Self {
+            tags: vec![],
+            moves: game.moves().map(|(_, mv)| mv.clone()).collect(),
+        };
+        record.set_tag("Layers", game.num_layers());
+        if game.shape() != BoardShape::default() {
+            record.set_tag("Size", game.shape().size);
+            record.set_tag("WinLength", game.shape().win_length);
+        }
+        if game.rules() != Rules::default() {
+            record.set_tag("Rules", game.rules());
+        }
+        record.set_tag("Circle", "?");
+        record.set_tag("Cross", "?");
+        record.set_tag("Result", result_to_string(game.outcome()));
+        record.set_tag("Date", "????.??.??");
+        if let Some(position) = game.start_position() {
+            record.set_tag("Position", position);
+        }
+        record
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a tag, keeping its position if it already exists.
+    pub fn set_tag(&mut self, name: &str, value: impl ToString) {
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The result from the `Result` tag, `None` for unfinished games or if it is missing.
+    pub fn result(&self) -> Option<Outcome> {
+        match self.tag("Result")? {
+            "1-0" => Some(Outcome::Winner(State::Circle)),
+            "0-1" => Some(Outcome::Winner(State::Cross)),
+            "0-0-1" => Some(Outcome::Winner(State::Triangle)),
+            "0-0-0-1" => Some(Outcome::Winner(State::Square)),
+            "1/2-1/2" => Some(Outcome::Stalemate),
+            _ => None,
+        }
+    }
+
+    /// Plays the recorded moves on a new game with the number of layers from the `Layers` tag and
+    /// the board shape from the `Size` and `WinLength` tags, or on the position from the
+    /// `Position` tag.
+    pub fn replay(&self) -> Result<Game, RecordError> {
+        let rules = match self.tag("Rules") {
+            Some(rules) => rules.parse().map_err(RecordError::InvalidRules)?,
+            None => Rules::default(),
+        };
+        let mut game = match self.tag("Position") {
+            Some(position) => {
+                let position: Position = position.parse().map_err(RecordError::InvalidPosition)?;
+                position
+                    .check_rules(rules)
+                    .map_err(RecordError::InvalidPosition)?;
+                Game::from_position(position)
+            }
+            None => {
+                let num_layers = self
+                    .tag("Layers")
+                    .and_then(|layers| layers.parse().ok())
+                    .filter(|&layers| layers > 0)
+                    .ok_or(RecordError::MissingLayers)?;
+                let shape = self.shape()?;
+                if !shape.fits(num_layers) {
+                    return Err(RecordError::TooLarge);
+                }
+                Game::with_shape(num_layers, shape)
+            }
+        };
+        game.set_rules(rules);
+        for (i, mv) in self.moves.iter().enumerate() {
+            game.play(mv)
+                .map_err(|error| RecordError::IllegalMove(i + 1, error))?;
+        }
+        Ok(game)
+    }
+
+    fn shape(&self) -> Result<BoardShape, RecordError> {
+        let size = match self.tag("Size") {
+            Some(size) => size.parse().ok().filter(|&size| size > 0),
+            None => Some(BoardShape::default().size),
+        }
+        .ok_or(RecordError::InvalidShape)?;
+        let win_length = match self.tag("WinLength") {
+            Some(win_length) => win_length.parse().ok(),
+            None => Some(size),
+        }
+        .filter(|win_length| (1..=size).contains(win_length))
+        .ok_or(RecordError::InvalidShape)?;
+        Ok(BoardShape { size, win_length })
+    }
+}
+
+pub(crate) fn result_to_string(outcome: Option<Outcome>) -> &'static str {
+    match outcome {
+        Some(Outcome::Winner(State::Circle)) => "1-0",
+        Some(Outcome::Winner(State::Cross)) => "0-1",
+        Some(Outcome::Winner(State::Triangle)) => "0-0-1",
+        Some(Outcome::Winner(State::Square)) => "0-0-0-1",
+        Some(Outcome::Stalemate) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+impl std::fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(
+                f,
+                "[{name} \"{}\"]",
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+        for (i, mv) in self.moves.iter().enumerate() {
+            if i % 2 == 0 {
+                tokens.push(format!("{}.", i / 2 + 1));
+            }
+            tokens.push(format!("{mv:#}"));
+        }
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        // wrap lines like pgn does
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > 80 {
+                writeln!(f)?;
+                line_length = 0;
+            } else if line_length > 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+            write!(f, "{token}")?;
+            line_length += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    /// The tag on this line, counting from 1, could not be read.
+    InvalidTag(usize),
+    InvalidMove(ParseCellPathError),
+    InvalidPosition(ParsePositionError),
+    MissingLayers,
+    /// The `Size` or `WinLength` tag is not a valid board shape.
+    InvalidShape,
+    /// The game would have more than [`BoardShape::MAX_CELLS`] cells or
+    /// [`BoardShape::MAX_LAYERS`] layers.
+    TooLarge,
+    InvalidRules(ParseRulesError),
+    /// The move with this number, counting from 1, could not be played.
+    IllegalMove(usize, MoveError),
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::InvalidTag(line) => write!(f, "the tag on line {line} is invalid"),
+            RecordError::InvalidMove(error) => write!(f, "{error}"),
+            RecordError::InvalidPosition(error) => write!(f, "{error}"),
+            RecordError::MissingLayers => write!(f, "the Layers tag is missing or invalid"),
+            RecordError::InvalidShape => write!(f, "the Size or WinLength tag is invalid"),
+            RecordError::TooLarge => write!(f, "the board is too large"),
+            RecordError::InvalidRules(error) => write!(f, "{error}"),
+            RecordError::IllegalMove(number, error) => {
+                write!(f, "move {number} is illegal: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl std::str::FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut record = Self::default();
+        let mut movetext = String::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if let Some(tag) = line.strip_prefix('[') {
+                let (name, value) = tag
+                    .strip_suffix(']')
+                    .and_then(|tag| tag.split_once(char::is_whitespace))
+                    .and_then(|(name, value)| {
+                        Some((name, value.trim().strip_prefix('"')?.strip_suffix('"')?))
+                    })
+                    .ok_or(RecordError::InvalidTag(i + 1))?;
+                let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+                record.set_tag(name, value);
+            } else {
+                movetext.push_str(line);
+                movetext.push(' ');
+            }
+        }
+
+        // cell numbers only name cells on 3 by 3 boards
+        let size = match record.tag("Size") {
+            Some(size) => size.parse().unwrap_or(0),
+            None => BoardShape::default().size,
+        };
+        let mut in_comment = false;
+        for token in movetext.split_whitespace() {
+            if in_comment || token.starts_with('{') {
+                in_comment = !token.ends_with('}');
+                continue;
+            }
+            let is_move_number =
+                token.ends_with('.') && token[..token.len() - 1].parse::<usize>().is_ok();
+            let is_result = matches!(token, "1-0" | "0-1" | "0-0-1" | "0-0-0-1" | "1/2-1/2" | "*");
+            if is_result && record.tag("Result").is_none() {
+                record.set_tag("Result", token);
+            } else if !is_move_number && !is_result {
+                record
+                    .moves
+                    .push(CellPath::parse_sized(token, size).map_err(RecordError::InvalidMove)?);
+            }
+        }
+        Ok(record)
+    }
+}
+
+/// Today's date in the `YYYY.MM.DD` form used by the `Date` tag.
+pub fn today() -> String {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() / 86400) as i64;
+
+    // days to a civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_and_import() {
+        let mut game = Game::new(2);
+        for mv in ["5.7", "7.5", "5.8", "8.5", "5.9"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        let mut record = GameRecord::from_game(&game);
+        record.set_tag("Circle", "Alice \"A\"");
+        record.set_tag("Date", "2023.04.01");
+
+        let text = record.to_string();
+        assert_eq!(
+            text,
+            "[Layers \"2\"]\n\
+             [Circle \"Alice \\\"A\\\"\"]\n\
+             [Cross \"?\"]\n\
+             [Result \"*\"]\n\
+             [Date \"2023.04.01\"]\n\
+             \n\
+             1. b2/a1 a1/b2 2. b2/b1 b1/b2 3. b2/c1 *\n"
+        );
+
+        let imported: GameRecord = text.parse().unwrap();
+        assert_eq!(imported, record);
+        assert!(imported.replay().unwrap().moves().eq(game.moves()));
+    }
+
+    #[test]
+    fn import_with_comments_and_errors() {
+        let record: GameRecord = "[Layers \"1\"]\n\n1. b2 {the center} a1 2. c3 1/2-1/2\n"
+            .parse()
+            .unwrap();
+        assert_eq!(record.moves.len(), 3);
+        assert_eq!(record.result(), Some(Outcome::Stalemate));
+        assert_eq!(record.replay().unwrap().num_moves(), 3);
+
+        let record: GameRecord = "[Layers \"1\"]\n1. b2 b2 *".parse().unwrap();
+        assert_eq!(
+            record.replay().err(),
+            Some(RecordError::IllegalMove(2, MoveError::CellTaken))
+        );
+        let record: GameRecord = "[Layers \"1\"]\n[Size \"4\"]\n[WinLength \"5\"]\n*"
+            .parse()
+            .unwrap();
+        assert_eq!(record.replay().err(), Some(RecordError::InvalidShape));
+        assert!("[Layers \"1\"]\n[Size \"4\"]\n1. 1 *"
+            .parse::<GameRecord>()
+            .is_err());
+        let record: GameRecord = "[Layers \"30\"]\n*".parse().unwrap();
+        assert_eq!(record.replay().err(), Some(RecordError::TooLarge));
+        assert_eq!(
+            "[Layers 2]".parse::<GameRecord>(),
+            Err(RecordError::InvalidTag(1))
+        );
+    }
+}
@@ -9,28 +9,34 @@ pub struct RenderState {
     per_object_vertex_buffer: wgpu::Buffer,
     per_object_vertex_buffer_count: usize,
     per_object_vertex_buffer_max_size: usize,
+    /// The version of the objects in the per object vertex buffer.
+    per_object_data_version: Option<u64>,
     vertices: wgpu::Buffer,
     indices: wgpu::Buffer,
     index_count: usize,
     render_pipeline: wgpu::RenderPipeline,
+    sample_count: u32,
 }
 
 impl RenderState {
-    pub fn new(wgpu_render_state: &eframe::egui_wgpu::RenderState) -> Self {
+    /// Sets up drawing into targets of `target_format`, like the window of the
+    /// [`App`](crate::App) or an [`OffscreenRenderer`](crate::OffscreenRenderer), with
+    /// `sample_count` samples per pixel.
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
         let per_object_vertex_buffer =
-            wgpu_render_state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Per Object Vertex Buffer"),
-                    contents: &[],
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                });
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Per Object Vertex Buffer"),
+                contents: &[],
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
         let per_object_vertex_buffer_count = 0;
         let per_object_vertex_buffer_max_size = 0;
 
-        let shader = wgpu_render_state
-            .device
-            .create_shader_module(include_wgsl!("./shader.wgsl"));
+        let shader = device.create_shader_module(include_wgsl!("./shader.wgsl"));
 
         let vertex_data = [
             Vertex {
@@ -51,116 +57,96 @@ impl RenderState {
             },
         ];
 
-        let vertices =
-            wgpu_render_state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Circle Vertex Buffer"),
-                    contents: bytemuck::cast_slice(&vertex_data),
-                    usage: wgpu::BufferUsages::VERTEX,
-                });
+        let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Circle Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertex_data),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
 
         let index_data = [0, 1, 2, 0, 2, 3];
 
-        let indices =
-            wgpu_render_state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Circle Index Buffer"),
-                    contents: bytemuck::cast_slice(&index_data),
-                    usage: wgpu::BufferUsages::INDEX,
-                });
+        let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Circle Index Buffer"),
+            contents: bytemuck::cast_slice(&index_data),
+            usage: wgpu::BufferUsages::INDEX,
+        });
 
         let index_count = index_data.len();
 
         let camera_bind_group_layout =
-            wgpu_render_state
-                .device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: Some("Circle Bind Group Layout"),
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
-                });
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Circle Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
 
         let render_pipeline_layout =
-            wgpu_render_state
-                .device
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("Render Pipeline Layout"),
-                    bind_group_layouts: &[&camera_bind_group_layout],
-                    push_constant_ranges: &[],
-                });
-
-        let render_pipeline =
-            wgpu_render_state
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Render Pipeline"),
-                    layout: Some(&render_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &shader,
-                        entry_point: "vs_main",
-                        buffers: &[PerObjectData::layout(), Vertex::layout()],
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &shader,
-                        entry_point: "fs_main",
-                        targets: &[Some(wgpu::ColorTargetState {
-                            format: wgpu_render_state.target_format,
-                            blend: Some(wgpu::BlendState {
-                                color: wgpu::BlendComponent::REPLACE,
-                                alpha: wgpu::BlendComponent::REPLACE,
-                            }),
-                            write_mask: wgpu::ColorWrites::ALL,
-                        })],
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Cw,
-                        cull_mode: Some(wgpu::Face::Back),
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    multiview: None,
-                });
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[PerObjectData::layout(), Vertex::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    // the shader fades out the edges of the glyphs
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
 
         let camera_uniform_buffer = {
-            wgpu_render_state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Camera Uniform Buffer"),
-                    contents: &[0; <Camera as ShaderSize>::SHADER_SIZE.get() as _],
-                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-                })
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Uniform Buffer"),
+                contents: &[0; <Camera as ShaderSize>::SHADER_SIZE.get() as _],
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            })
         };
 
-        let camera_bind_group =
-            wgpu_render_state
-                .device
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("Camera Bind Group"),
-                    layout: &camera_bind_group_layout,
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: camera_uniform_buffer.as_entire_binding(),
-                    }],
-                });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera Bind Group"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_uniform_buffer.as_entire_binding(),
+            }],
+        });
 
         Self {
             camera_uniform_buffer,
@@ -168,40 +154,49 @@ impl RenderState {
             per_object_vertex_buffer,
             per_object_vertex_buffer_count,
             per_object_vertex_buffer_max_size,
+            per_object_data_version: None,
             vertices,
             indices,
             index_count,
             render_pipeline,
+            sample_count,
         }
     }
 
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Gets the objects and the camera ready for [`render`](Self::render). `version` has to
+    /// change whenever `data` does, the objects are only uploaded again when it did.
     pub fn prepare(
         &mut self,
         camera: Camera,
         data: &[PerObjectData],
+        version: u64,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         _encoder: &mut wgpu::CommandEncoder,
     ) {
-        if data.len() * std::mem::size_of::<PerObjectData>()
-            > self.per_object_vertex_buffer_max_size
-        {
-            self.per_object_vertex_buffer_max_size =
-                data.len() * std::mem::size_of::<PerObjectData>();
-            self.per_object_vertex_buffer =
-                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Per Object Vertex Buffer"),
-                    contents: bytemuck::cast_slice(data),
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                });
-        } else {
-            queue.write_buffer(
-                &self.per_object_vertex_buffer,
-                0,
-                bytemuck::cast_slice(data),
-            );
+        if self.per_object_data_version != Some(version) {
+            if std::mem::size_of_val(data) > self.per_object_vertex_buffer_max_size {
+                self.per_object_vertex_buffer_max_size = std::mem::size_of_val(data);
+                self.per_object_vertex_buffer =
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Per Object Vertex Buffer"),
+                        contents: bytemuck::cast_slice(data),
+                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    });
+            } else {
+                queue.write_buffer(
+                    &self.per_object_vertex_buffer,
+                    0,
+                    bytemuck::cast_slice(data),
+                );
+            }
+            self.per_object_vertex_buffer_count = data.len();
+            self.per_object_data_version = Some(version);
         }
-        self.per_object_vertex_buffer_count = data.len();
 
         let mut buffer = UniformBuffer::new([0; <Camera as ShaderSize>::SHADER_SIZE.get() as _]);
         buffer.write(&camera).unwrap();
@@ -222,3 +217,208 @@ impl RenderState {
         );
     }
 }
+
+/// The textures the board is drawn into with more than one sample per pixel, and the resolved
+/// picture of it that is copied onto the window.
+struct MultisampleTarget {
+    size: [u32; 2],
+    multisampled_view: wgpu::TextureView,
+    resolved_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Draws the board into the window, either straight into the render pass of egui or, with
+/// multisampling, into a [`MultisampleTarget`] first, since egui draws with one sample.
+pub struct WindowRenderState {
+    render_state: RenderState,
+    target_format: wgpu::TextureFormat,
+    multisample_target: Option<MultisampleTarget>,
+    blit_bind_group_layout: wgpu::BindGroupLayout,
+    blit_pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+}
+
+impl WindowRenderState {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("./blit.wgsl"));
+
+        let blit_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Blit Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let blit_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&blit_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let blit_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&blit_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    // the resolved picture is transparent where there is no board
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Blit Sampler"),
+            ..Default::default()
+        });
+
+        Self {
+            render_state: RenderState::new(device, target_format, 1),
+            target_format,
+            multisample_target: None,
+            blit_bind_group_layout,
+            blit_pipeline,
+            sampler,
+        }
+    }
+
+    fn multisample_target(
+        &self,
+        device: &wgpu::Device,
+        size: [u32; 2],
+        sample_count: u32,
+    ) -> MultisampleTarget {
+        let create_view = |label, sample_count, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.target_format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let multisampled_view = create_view(
+            "Multisampled Texture",
+            sample_count,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let resolved_view = create_view(
+            "Resolved Texture",
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &self.blit_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&resolved_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        MultisampleTarget {
+            size,
+            multisampled_view,
+            resolved_view,
+            bind_group,
+        }
+    }
+
+    /// Like [`RenderState::prepare`], but with multisampling the board is also drawn already,
+    /// into a texture of `size` pixels with `sample_count` samples each.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        camera: Camera,
+        data: &[PerObjectData],
+        version: u64,
+        sample_count: u32,
+        size: [u32; 2],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if self.render_state.sample_count() != sample_count {
+            self.render_state = RenderState::new(device, self.target_format, sample_count);
+            self.multisample_target = None;
+        }
+        self.render_state
+            .prepare(camera, data, version, device, queue, encoder);
+        if sample_count == 1 {
+            return;
+        }
+
+        let size = size.map(|n| n.max(1));
+        if !matches!(&self.multisample_target, Some(target) if target.size == size) {
+            self.multisample_target = Some(self.multisample_target(device, size, sample_count));
+        }
+        let target = self.multisample_target.as_ref().unwrap();
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Multisampled Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.multisampled_view,
+                resolve_target: Some(&target.resolved_view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    // only the resolved samples are needed
+                    store: false,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        self.render_state.render(&mut render_pass);
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        match &self.multisample_target {
+            Some(target) if self.render_state.sample_count() > 1 => {
+                render_pass.set_pipeline(&self.blit_pipeline);
+                render_pass.set_bind_group(0, &target.bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+            _ => self.render_state.render(render_pass),
+        }
+    }
+}
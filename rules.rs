@@ -0,0 +1,204 @@
+use crate::{Board, CellPath, Element, State};
+
+/// House rules that change who plays, how boards are won and where can be played, chosen when a
+/// game starts. The default is the usual game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rules {
+    /// From 2 to 4, the players take turns in the order of [`State::ALL`].
+    pub num_players: usize,
+    /// Getting a line loses the board, so it goes to the player whose turn comes next.
+    pub misere: bool,
+    /// Boards that have been won can still be played in and sent to until they are full, which
+    /// does not change who won them.
+    pub play_in_won_boards: bool,
+    /// Drawn sub-boards count for every player's lines rather than blocking them.
+    pub wild_draws: bool,
+    /// A full board of sub-boards without a line goes to the player that won the most of them.
+    pub majority_tiebreak: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            num_players: 2,
+            misere: false,
+            play_in_won_boards: false,
+            wild_draws: false,
+            majority_tiebreak: false,
+        }
+    }
+}
+
+impl Rules {
+    /// The players in this game, in the order they take turns.
+    pub fn players(&self) -> &'static [State] {
+        &State::ALL[..self.num_players]
+    }
+}
+
+/// The names of the rules that are on separated by spaces, `standard` if none are. Games with
+/// more than 2 players start with `players=` and the number of players.
+impl std::fmt::Display for Rules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let players = format!("players={}", self.num_players);
+        let names: Vec<_> = [
+            (self.num_players != 2, players.as_str()),
+            (self.misere, "misere"),
+            (self.play_in_won_boards, "play_in_won_boards"),
+            (self.wild_draws, "wild_draws"),
+            (self.majority_tiebreak, "majority_tiebreak"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect();
+        if names.is_empty() {
+            write!(f, "standard")
+        } else {
+            write!(f, "{}", names.join(" "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRulesError(String);
+
+impl std::fmt::Display for ParseRulesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown rule `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseRulesError {}
+
+impl std::str::FromStr for Rules {
+    type Err = ParseRulesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules::default();
+        for name in s.split_whitespace() {
+            match name {
+                "standard" => {}
+                "misere" => rules.misere = true,
+                "play_in_won_boards" => rules.play_in_won_boards = true,
+                "wild_draws" => rules.wild_draws = true,
+                "majority_tiebreak" => rules.majority_tiebreak = true,
+                _ => {
+                    rules.num_players = name
+                        .strip_prefix("players=")
+                        .and_then(|num_players| num_players.parse().ok())
+                        .filter(|num_players| (2..=State::ALL.len()).contains(num_players))
+                        .ok_or_else(|| ParseRulesError(name.to_string()))?
+                }
+            }
+        }
+        Ok(rules)
+    }
+}
+
+/// Returns the path of the board the next move has to be played in.
+///
+/// The last move's coordinates at every nesting level, minus the outermost one, name the board
+/// the opponent is sent to. On a 2 layer board this is the usual Ultimate Tic Tac Toe rule, a
+/// move in cell (x, y) of a sub-board sends the opponent to sub-board (x, y). If the board that
+/// is sent to is already won or full, the opponent may play anywhere in its parent instead, and
+/// so on up to the whole board.
+pub fn get_target_board(board: &Board, last_move: Option<&CellPath>) -> CellPath {
+    let target: CellPath = match last_move.map(|path| &path[..]) {
+        Some([_, rest @ ..]) => rest.iter().copied().collect(),
+        _ => CellPath::new(),
+    };
+    get_playable_board(board, target)
+}
+
+/// Returns `target` if it can be played in, otherwise the closest of its parents that can.
+pub fn get_playable_board(board: &Board, mut target: CellPath) -> CellPath {
+    while !target.is_empty() && !is_playable(board, &target) {
+        target.pop();
+    }
+    target
+}
+
+/// Whether the board at `path` still has cells that can be played in.
+pub fn is_playable(board: &Board, path: &CellPath) -> bool {
+    let rules = board.rules();
+    let mut board = board;
+    for &(x, y) in path.iter() {
+        match &board.elements()[x][y] {
+            Element::Board(sub_board) => board = sub_board,
+            Element::Won(_, sub_board) if rules.play_in_won_boards => board = sub_board,
+            Element::State(_) | Element::Won(_, _) => return false,
+        }
+    }
+    if rules.play_in_won_boards {
+        board.num_moves_left() > 0
+    } else {
+        board.get_winner().is_none() && !board.is_stalemate()
+    }
+}
+
+/// Whether a move at `path` is inside of the board at `target`.
+pub fn is_move_in_target(path: &CellPath, target: &CellPath) -> bool {
+    path.starts_with(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_board() -> Board {
+        "(3/3/3)(3/3/3)(3/3/3)/(3/3/3)(3/3/3)(3/3/3)/(3/3/3)(3/3/3)(3/3/3)"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn sent_to_sub_board() {
+        let board = new_board();
+        assert_eq!(get_target_board(&board, None), CellPath::new());
+        assert_eq!(
+            get_target_board(&board, Some(&"1.6".parse().unwrap())).to_string(),
+            "6"
+        );
+        assert!(is_move_in_target(
+            &"6.1".parse().unwrap(),
+            &"6".parse().unwrap()
+        ));
+        assert!(!is_move_in_target(
+            &"5.1".parse().unwrap(),
+            &"6".parse().unwrap()
+        ));
+    }
+
+    #[test]
+    fn free_choice_when_sub_board_won() {
+        let mut board = new_board();
+        board.set(&[(2, 1)], Element::Won(State::Cross, Box::default()));
+        assert_eq!(
+            get_target_board(&board, Some(&"1.6".parse().unwrap())),
+            CellPath::new()
+        );
+    }
+
+    #[test]
+    fn rule_names() {
+        let rules = Rules {
+            misere: true,
+            wild_draws: true,
+            ..Default::default()
+        };
+        assert_eq!(rules.to_string(), "misere wild_draws");
+        assert_eq!("misere wild_draws".parse(), Ok(rules));
+        assert_eq!("standard".parse(), Ok(Rules::default()));
+        let rules = Rules {
+            num_players: 3,
+            ..Default::default()
+        };
+        assert_eq!(rules.to_string(), "players=3");
+        assert_eq!("players=3".parse(), Ok(rules));
+        assert!("players=5".parse::<Rules>().is_err());
+        assert_eq!(
+            "suicide".parse::<Rules>(),
+            Err(ParseRulesError("suicide".to_string()))
+        );
+    }
+}
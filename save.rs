@@ -0,0 +1,339 @@
+use crate::{
+    BoardShape, CellPath, Game, Move, MoveError, ParseCellPathError, ParsePositionError, Position,
+    Rules, State,
+};
+
+/// A game as it is written to disk.
+///
+/// The format is plain text, one `key value` entry per line after a header line naming the
+/// format version:
+///
+/// ```text
+/// ultimate-tic-tac-toe 1
+/// layers 2
+/// turn Cross
+/// moves 5.7 7.5 5.8
+/// setting vs_computer true
+/// ```
+///
+/// `moves` lists every move from the start of the game as a [`CellPath`], in the alternate form on
+/// boards that are not 3 by 3, and `turn` is the player to move after them, which is checked when
+/// loading. Each `setting` entry is a name and a value for the ui. Lines starting with `#` and entries with unknown keys are
+/// skipped, so older versions can still load newer files as long as the version in the header is
+/// one they know. The version is only bumped for changes that older versions would misread.
+///
+/// A game that was set up from a [`Position`] has a `position` entry that the moves start from.
+/// Version 1 does not know it, so only those files are written as version 2. Games on boards other
+/// than 3 by 3 with 3 in a row to win have `size` and `win_length` entries, which only version 3
+/// knows. Games with other [`Rules`] have a `rules` entry, which only version 4 knows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveFile {
+    pub num_layers: usize,
+    pub shape: BoardShape,
+    pub rules: Rules,
+    pub position: Option<Position>,
+    pub moves: Vec<Move>,
+    pub turn: Option<State>,
+    pub settings: Vec<(String, String)>,
+}
+
+impl SaveFile {
+    pub const HEADER: &str = "ultimate-tic-tac-toe";
+    pub const VERSION: u32 = 4;
+
+    pub fn from_game(game: &Game) -> Self {
+        Self {
+            num_layers: game.num_layers(),
+            shape: game.shape(),
+            rules: game.rules(),
+            position: game.start_position().cloned(),
+            moves: game.moves().map(|(_, mv)| mv.clone()).collect(),
+            turn: Some(game.current_player()),
+            settings: vec![],
+        }
+    }
+
+    /// Replays the saved moves on a new game.
+    pub fn to_game(&self) -> Result<Game, LoadError> {
+        let mut game = match &self.position {
+            Some(position) => {
+                position
+                    .check_rules(self.rules)
+                    .map_err(LoadError::InvalidPosition)?;
+                Game::from_position(position.clone())
+            }
+            None if self.num_layers == 0 => return Err(LoadError::MissingLayers),
+            None if !(1..=self.shape.size).contains(&self.shape.win_length) => {
+                return Err(LoadError::InvalidShape)
+            }
+            None if !self.shape.fits(self.num_layers) => return Err(LoadError::TooLarge),
+            None => Game::with_shape(self.num_layers, self.shape),
+        };
+        game.set_rules(self.rules);
+        for (i, mv) in self.moves.iter().enumerate() {
+            game.play(mv)
+                .map_err(|error| LoadError::IllegalMove(i + 1, error))?;
+        }
+        match self.turn {
+            Some(turn) if turn != game.current_player() => Err(LoadError::WrongTurn(turn)),
+            _ => Ok(game),
+        }
+    }
+
+    /// The number of cells along each side of the boards, from the position if there is one.
+    fn board_size(&self) -> usize {
+        match &self.position {
+            Some(position) => position.board.shape().size,
+            None => self.shape.size,
+        }
+    }
+
+    pub fn setting(&self, name: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_setting(&mut self, name: &str, value: impl ToString) {
+        self.settings.retain(|(key, _)| key != name);
+        self.settings.push((name.to_string(), value.to_string()));
+    }
+}
+
+impl std::fmt::Display for SaveFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let version = if self.rules != Rules::default() {
+            4
+        } else if self.shape != BoardShape::default() {
+            3
+        } else if self.position.is_some() {
+            2
+        } else {
+            1
+        };
+        writeln!(f, "{} {version}", Self::HEADER)?;
+        writeln!(f, "layers {}", self.num_layers)?;
+        if self.shape != BoardShape::default() {
+            writeln!(f, "size {}", self.shape.size)?;
+            writeln!(f, "win_length {}", self.shape.win_length)?;
+        }
+        if self.rules != Rules::default() {
+            writeln!(f, "rules {}", self.rules)?;
+        }
+        if let Some(position) = &self.position {
+            writeln!(f, "position {position}")?;
+        }
+        if let Some(turn) = self.turn {
+            writeln!(f, "turn {turn}")?;
+        }
+        write!(f, "moves")?;
+        for mv in &self.moves {
+            write!(f, " {}", mv.display(self.board_size()))?;
+        }
+        writeln!(f)?;
+        for (name, value) in &self.settings {
+            writeln!(f, "setting {name} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    MissingHeader,
+    UnsupportedVersion(String),
+    MissingLayers,
+    /// The size is 0 or the number of cells in a row needed to win does not fit on the board.
+    InvalidShape,
+    /// The game would have more than [`BoardShape::MAX_CELLS`] cells or
+    /// [`BoardShape::MAX_LAYERS`] layers.
+    TooLarge,
+    InvalidLine(usize),
+    InvalidMove(ParseCellPathError),
+    InvalidPosition(ParsePositionError),
+    /// The move with this number, counting from 1, could not be played.
+    IllegalMove(usize, MoveError),
+    WrongTurn(State),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::MissingHeader => write!(f, "not a saved game"),
+            LoadError::UnsupportedVersion(version) => {
+                write!(f, "saved games of version {version} are not supported")
+            }
+            LoadError::MissingLayers => write!(f, "the number of layers is missing"),
+            LoadError::InvalidShape => write!(f, "the board size or win length is invalid"),
+            LoadError::TooLarge => write!(f, "the board is too large"),
+            LoadError::InvalidLine(line) => write!(f, "line {line} is invalid"),
+            LoadError::InvalidMove(error) => write!(f, "{error}"),
+            LoadError::InvalidPosition(error) => write!(f, "{error}"),
+            LoadError::IllegalMove(number, error) => write!(f, "move {number} is illegal: {error}"),
+            LoadError::WrongTurn(turn) => {
+                write!(f, "the game says it is {turn}'s turn, but it is not")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl std::str::FromStr for SaveFile {
+    type Err = LoadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate().filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        });
+
+        let version = lines
+            .next()
+            .and_then(|(_, line)| line.trim().strip_prefix(Self::HEADER))
+            .ok_or(LoadError::MissingHeader)?
+            .trim();
+        if !matches!(version.parse::<u32>(), Ok(1..=Self::VERSION)) {
+            return Err(LoadError::UnsupportedVersion(version.to_string()));
+        }
+
+        let mut save_file = Self::default();
+        // moves are read last, since how they are written depends on the board size
+        let mut moves = "";
+        for (i, line) in lines {
+            let invalid_line = || LoadError::InvalidLine(i + 1);
+            let (key, value) = line
+                .trim()
+                .split_once(char::is_whitespace)
+                .unwrap_or((line.trim(), ""));
+            let value = value.trim();
+            match key {
+                "layers" => save_file.num_layers = value.parse().map_err(|_| invalid_line())?,
+                "size" => save_file.shape.size = value.parse().map_err(|_| invalid_line())?,
+                "win_length" => {
+                    save_file.shape.win_length = value.parse().map_err(|_| invalid_line())?
+                }
+                "rules" => save_file.rules = value.parse().map_err(|_| invalid_line())?,
+                "position" => {
+                    save_file.position = Some(value.parse().map_err(LoadError::InvalidPosition)?)
+                }
+                "turn" => save_file.turn = Some(value.parse().map_err(|_| invalid_line())?),
+                "moves" => moves = value,
+                "setting" => {
+                    let (name, value) =
+                        value.split_once(char::is_whitespace).unwrap_or((value, ""));
+                    save_file.set_setting(name, value.trim());
+                }
+                _ => {} // written by a newer version
+            }
+        }
+        save_file.moves = moves
+            .split_whitespace()
+            .map(|mv| CellPath::parse_sized(mv, save_file.board_size()))
+            .collect::<Result<_, _>>()
+            .map_err(LoadError::InvalidMove)?;
+        Ok(save_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut game = Game::new(2);
+        for mv in ["5.7", "7.5", "5.8"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        let mut save_file = SaveFile::from_game(&game);
+        save_file.set_setting("vs_computer", true);
+
+        let text = save_file.to_string();
+        assert_eq!(
+            text,
+            "ultimate-tic-tac-toe 1\nlayers 2\nturn Cross\nmoves 5.7 7.5 5.8\nsetting vs_computer true\n"
+        );
+        let loaded: SaveFile = text.parse().unwrap();
+        assert_eq!(loaded, save_file);
+        assert_eq!(loaded.setting("vs_computer"), Some("true"));
+        let loaded_game = loaded.to_game().unwrap();
+        assert!(loaded_game.moves().eq(game.moves()));
+    }
+
+    #[test]
+    fn game_from_position() {
+        let position: Position = "x2/1o1/3 o -".parse().unwrap();
+        let mut game = Game::from_position(position);
+        game.play(&"9".parse().unwrap()).unwrap();
+        let text = SaveFile::from_game(&game).to_string();
+        assert_eq!(
+            text,
+            "ultimate-tic-tac-toe 2\nlayers 1\nposition x2/1o1/3 o -\nturn Cross\nmoves 9\n"
+        );
+        let loaded = text.parse::<SaveFile>().unwrap().to_game().unwrap();
+        assert_eq!(loaded.position(), game.position());
+        assert_eq!(loaded.start_position(), game.start_position());
+        // nobody plays triangle in a game of two players
+        let text = "ultimate-tic-tac-toe 2\nlayers 1\nposition x2/1o1/3 t -\n";
+        assert!(matches!(
+            text.parse::<SaveFile>().unwrap().to_game(),
+            Err(LoadError::InvalidPosition(ParsePositionError::InvalidTurn(
+                _
+            )))
+        ));
+
+        let mut game = Game::with_shape(
+            1,
+            BoardShape {
+                size: 5,
+                win_length: 4,
+            },
+        );
+        game.set_rules(Rules {
+            misere: true,
+            ..Default::default()
+        });
+        game.play(&"a3".parse().unwrap()).unwrap();
+        let text = SaveFile::from_game(&game).to_string();
+        assert_eq!(
+            text,
+            "ultimate-tic-tac-toe 4\nlayers 1\nsize 5\nwin_length 4\nrules misere\nturn Cross\nmoves a3\n"
+        );
+        let loaded = text.parse::<SaveFile>().unwrap().to_game().unwrap();
+        assert_eq!(loaded.shape(), game.shape());
+        assert_eq!(loaded.rules(), game.rules());
+    }
+
+    #[test]
+    fn skips_unknown_entries() {
+        let save_file: SaveFile =
+            "# a comment\nultimate-tic-tac-toe 1\nlayers 1\nclock 5:00\nmoves 5\n"
+                .parse()
+                .unwrap();
+        assert_eq!(save_file.to_game().unwrap().num_moves(), 1);
+        assert_eq!(
+            "ultimate-tic-tac-toe 5\n".parse::<SaveFile>(),
+            Err(LoadError::UnsupportedVersion("5".to_string()))
+        );
+        assert_eq!(
+            "ultimate-tic-tac-toe 1\nlayers 1\nmoves 5 5\n"
+                .parse::<SaveFile>()
+                .unwrap()
+                .to_game()
+                .err(),
+            Some(LoadError::IllegalMove(2, MoveError::CellTaken))
+        );
+        for text in ["layers 20\n", "layers 2\nsize 1000\n"] {
+            assert_eq!(
+                format!("ultimate-tic-tac-toe 4\n{text}")
+                    .parse::<SaveFile>()
+                    .unwrap()
+                    .to_game()
+                    .err(),
+                Some(LoadError::TooLarge)
+            );
+        }
+    }
+}
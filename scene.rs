@@ -0,0 +1,610 @@
+use crate::{Board, Camera, CellPath, Element, Game, Glyph, Move, MoveStats, PerObjectData, State};
+
+/// The mark a player's cells are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    Circle,
+    Cross,
+    Triangle,
+    Square,
+}
+
+impl Symbol {
+    pub const ALL: [Symbol; 4] = [
+        Symbol::Circle,
+        Symbol::Cross,
+        Symbol::Triangle,
+        Symbol::Square,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Symbol::Circle => "circle",
+            Symbol::Cross => "cross",
+            Symbol::Triangle => "triangle",
+            Symbol::Square => "square",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|symbol| symbol.name() == name)
+    }
+}
+
+/// How the cells of a player are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerStyle {
+    pub symbol: Symbol,
+    pub color: [f32; 3],
+}
+
+impl PlayerStyle {
+    /// Players are drawn with the symbol they are named after by default.
+    pub fn default_for(player: State) -> Self {
+        match player {
+            State::Circle => Self {
+                symbol: Symbol::Circle,
+                color: [0.0, 0.0, 1.0],
+            },
+            State::Cross => Self {
+                symbol: Symbol::Cross,
+                color: [1.0, 0.0, 0.0],
+            },
+            State::Triangle => Self {
+                symbol: Symbol::Triangle,
+                color: [0.0, 0.7, 0.0],
+            },
+            State::Square => Self {
+                symbol: Symbol::Square,
+                color: [0.9, 0.7, 0.0],
+            },
+        }
+    }
+}
+
+/// Boards that are smaller than this many pixels on screen are drawn as a single tile.
+const MIN_BOARD_PIXELS: f32 = 6.0;
+
+/// The color of tiles for boards nobody won, about as bright as their grid lines on average.
+const NEUTRAL: [f32; 3] = [0.1, 0.1, 0.1];
+
+/// The part of the world a camera sees, so that what lies outside of it or is too small to make
+/// out can be left out.
+#[derive(Debug, Clone, Copy)]
+struct View {
+    min: cgmath::Vector2<f32>,
+    max: cgmath::Vector2<f32>,
+    pixels_per_unit: f32,
+}
+
+impl View {
+    fn new(camera: &Camera) -> Self {
+        let aspect = camera.screen_size.x / camera.screen_size.y;
+        // the screen turned back by the rotation of the camera, as a box around it
+        let (sin, cos) = camera.rotation.sin_cos();
+        let turned = sin.abs() + cos.abs();
+        let half_size = cgmath::vec2(turned * aspect, turned) / camera.scale;
+        Self {
+            min: camera.position - half_size,
+            max: camera.position + half_size,
+            pixels_per_unit: camera.scale * camera.screen_size.y / 2.0,
+        }
+    }
+
+    /// Whether any of the rectangle at `position` of `size` can be seen.
+    fn sees(&self, position: cgmath::Vector2<f32>, size: cgmath::Vector2<f32>) -> bool {
+        let min = position - size / 2.0;
+        let max = position + size / 2.0;
+        min.x < self.max.x && max.x > self.min.x && min.y < self.max.y && max.y > self.min.y
+    }
+}
+
+/// What the objects of a board depend on besides the board and where it is drawn.
+#[derive(Clone, Copy)]
+struct Looks<'a> {
+    target_board: &'a CellPath,
+    styles: &'a [PlayerStyle; 4],
+}
+
+/// The objects of one board and everything on it, kept so that boards which did not change
+/// don't have to be built again.
+#[derive(Debug, Clone)]
+struct BoardNode {
+    path: CellPath,
+    position: cgmath::Vector2<f32>,
+    scale: cgmath::Vector2<f32>,
+    /// The size of the whole board in the world.
+    extent: cgmath::Vector2<f32>,
+    dimmed: bool,
+    highlight: Option<PerObjectData>,
+    /// Indexed like the elements of the board, by x and then y.
+    cells: Vec<Vec<CellNode>>,
+    grid: Vec<PerObjectData>,
+}
+
+#[derive(Debug, Clone)]
+enum CellNode {
+    /// The mark of a player, nothing for an empty cell.
+    Mark(Option<State>, Vec<PerObjectData>),
+    Board(BoardNode),
+    /// A finished board, which is drawn faded out with the mark of its winner on top.
+    Won(State, BoardNode, Vec<PerObjectData>),
+}
+
+impl BoardNode {
+    fn build(
+        board: &Board,
+        position: cgmath::Vector2<f32>,
+        scale: cgmath::Vector2<f32>,
+        path: CellPath,
+        dimmed: bool,
+        looks: Looks<'_>,
+    ) -> Self {
+        let mut node = Self {
+            path,
+            position,
+            scale,
+            extent: scale * board.shape().size as f32,
+            dimmed,
+            highlight: None,
+            cells: vec![],
+            grid: vec![],
+        };
+        node.highlight = node.highlight(board, looks);
+        node.cells = board
+            .elements()
+            .iter()
+            .enumerate()
+            .map(|(x, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .map(|(y, element)| node.build_cell(board, (x, y), element, looks))
+                    .collect()
+            })
+            .collect();
+        node.grid = node.grid(board);
+        node
+    }
+
+    fn brightness(&self) -> f32 {
+        if self.dimmed {
+            0.3
+        } else {
+            1.0
+        }
+    }
+
+    fn highlight(&self, board: &Board, looks: Looks<'_>) -> Option<PerObjectData> {
+        let size = board.shape().size as f32;
+        (!looks.target_board.is_empty() && self.path == *looks.target_board).then(|| {
+            PerObjectData {
+                object_position: self.position,
+                rotation: cgmath::Rad::from(cgmath::Deg(0.0)).0,
+                scale: size * self.scale,
+                color: (0.1, 0.25, 0.1).into(),
+                glyph: Glyph::Filled as u32,
+                line_width: 0.0,
+            }
+        })
+    }
+
+    fn grid(&self, board: &Board) -> Vec<PerObjectData> {
+        let size = board.shape().size as f32;
+        let (position, scale) = (self.position, self.scale);
+        let color = cgmath::vec3(0.2, 0.2, 0.2) * self.brightness();
+        let mut grid = vec![];
+        for x in 0..=board.shape().size {
+            grid.push(PerObjectData {
+                object_position: position + cgmath::vec2((x as f32 - size / 2.0) * scale.x, 0.0),
+                rotation: cgmath::Rad::from(cgmath::Deg(0.0)).0,
+                scale: cgmath::vec2(0.05 * scale.x, (size + 0.05) * scale.y),
+                color,
+                glyph: Glyph::Filled as u32,
+                line_width: 0.0,
+            });
+        }
+        for y in 0..=board.shape().size {
+            grid.push(PerObjectData {
+                object_position: position + cgmath::vec2(0.0, (y as f32 - size / 2.0) * scale.y),
+                rotation: cgmath::Rad::from(cgmath::Deg(0.0)).0,
+                scale: cgmath::vec2((size + 0.05) * scale.x, 0.05 * scale.y),
+                color,
+                glyph: Glyph::Filled as u32,
+                line_width: 0.0,
+            });
+        }
+        grid
+    }
+
+    fn build_cell(
+        &self,
+        board: &Board,
+        (x, y): (usize, usize),
+        element: &Element,
+        looks: Looks<'_>,
+    ) -> CellNode {
+        let size = board.shape().size as f32;
+        let offset = (size - 1.0) / 2.0;
+        let scale = self.scale;
+        let position = self.position
+            + cgmath::vec2((x as f32 - offset) * scale.x, (y as f32 - offset) * scale.y);
+        let path = self.path.child((x, y));
+        match element {
+            Element::State(None) => CellNode::Mark(None, vec![]),
+            &Element::State(Some(state)) => {
+                let mut mark = vec![];
+                let style = looks.styles[state as usize];
+                render_state(style, position, scale, self.brightness(), &mut mark);
+                CellNode::Mark(Some(state), mark)
+            }
+            Element::Board(board) => CellNode::Board(BoardNode::build(
+                board,
+                position,
+                scale / size,
+                path,
+                self.dimmed,
+                looks,
+            )),
+            &Element::Won(winner, ref board) => {
+                let board = BoardNode::build(board, position, scale / size, path, true, looks);
+                let mut mark = vec![];
+                let style = looks.styles[winner as usize];
+                render_state(style, position, scale, self.brightness(), &mut mark);
+                CellNode::Won(winner, board, mark)
+            }
+        }
+    }
+
+    /// Builds the cells on `path` that differ from `board` again, where `old_target` is the
+    /// target board of the last update. A move only changes its cell and the boards it wins on
+    /// the way to it, so walking its path is enough. Returns whether anything changed.
+    fn update_path(
+        &mut self,
+        board: &Board,
+        path: &[(usize, usize)],
+        old_target: &CellPath,
+        looks: Looks<'_>,
+    ) -> bool {
+        let mut changed = false;
+        if old_target != looks.target_board
+            && (self.path == *old_target || self.path == *looks.target_board)
+        {
+            self.highlight = self.highlight(board, looks);
+            changed = true;
+        }
+
+        let Some((&(x, y), rest)) = path.split_first() else {
+            return changed;
+        };
+        let element = &board.elements()[x][y];
+        changed |= match (&mut self.cells[x][y], element) {
+            (CellNode::Mark(state, _), &Element::State(new_state)) if *state == new_state => false,
+            (CellNode::Board(node), Element::Board(sub_board)) => {
+                node.update_path(sub_board, rest, old_target, looks)
+            }
+            (CellNode::Won(winner, node, _), &Element::Won(new_winner, ref sub_board))
+                if *winner == new_winner =>
+            {
+                node.update_path(sub_board, rest, old_target, looks)
+            }
+            _ => {
+                self.cells[x][y] = self.build_cell(board, (x, y), element, looks);
+                true
+            }
+        };
+        changed
+    }
+
+    /// Adds the objects in the order they are drawn, the grid goes on top of the cells. Boards
+    /// that `view` doesn't see are left out, and boards that are too small for it are drawn as a
+    /// tile of `tile_color`, unless they are the target board.
+    fn write(
+        &self,
+        view: Option<&View>,
+        tile_color: cgmath::Vector3<f32>,
+        per_object_data: &mut Vec<PerObjectData>,
+    ) {
+        if let Some(view) = view {
+            if !view.sees(self.position, self.extent) {
+                return;
+            }
+            if self.extent.x.max(self.extent.y) * view.pixels_per_unit < MIN_BOARD_PIXELS {
+                let tile = self.highlight.unwrap_or(PerObjectData {
+                    object_position: self.position,
+                    rotation: 0.0,
+                    scale: self.extent,
+                    color: tile_color,
+                    glyph: Glyph::Filled as u32,
+                    line_width: 0.0,
+                });
+                per_object_data.push(tile);
+                return;
+            }
+        }
+
+        per_object_data.extend(self.highlight);
+        let neutral = cgmath::Vector3::from(NEUTRAL) * self.brightness();
+        for cell in self.cells.iter().flatten() {
+            match cell {
+                CellNode::Mark(_, mark) => per_object_data.extend_from_slice(mark),
+                CellNode::Board(node) => node.write(view, neutral, per_object_data),
+                CellNode::Won(_, node, mark) => {
+                    let winner_color = mark.first().map_or(neutral, |object| object.color);
+                    node.write(view, winner_color, per_object_data);
+                    per_object_data.extend_from_slice(mark);
+                }
+            }
+        }
+        per_object_data.extend_from_slice(&self.grid);
+    }
+}
+
+/// Adds the objects that draw `board` and everything on it, centered on the origin with cells
+/// of 1 unit. The board at `target_board` is highlighted. With a `camera`, only what it sees is
+/// drawn, and boards that are just a few pixels big are drawn as tiles, so that the number of
+/// objects stays bounded on boards of any depth.
+pub fn render_board(
+    board: &Board,
+    target_board: &CellPath,
+    styles: &[PlayerStyle; 4],
+    camera: Option<&Camera>,
+    per_object_data: &mut Vec<PerObjectData>,
+) {
+    let looks = Looks {
+        target_board,
+        styles,
+    };
+    let root = BoardNode::build(
+        board,
+        (0.0, 0.0).into(),
+        (1.0, 1.0).into(),
+        CellPath::new(),
+        false,
+        looks,
+    );
+    let view = camera.map(View::new);
+    root.write(view.as_ref(), NEUTRAL.into(), per_object_data);
+}
+
+/// The objects that draw a whole board like [`render_board`], kept between frames.
+///
+/// Deep boards have a lot of objects, so [`update`](Self::update) only builds the boards that
+/// changed since the last update again, and tells whether anything changed at all so that
+/// uploading the objects can be skipped as well. The [`version`](Game::version) of the game tells
+/// whether it changed at all, and only the cells of the moves that were played or taken back
+/// since are looked at. Only what the camera sees is kept in
+/// [`data`](Self::data), like with [`render_board`].
+#[derive(Debug, Clone, Default)]
+pub struct BoardMesh {
+    /// What the mesh was built for.
+    start_version: Option<u64>,
+    version: Option<u64>,
+    moves: Vec<Move>,
+    target_board: CellPath,
+    styles: Option<[PlayerStyle; 4]>,
+    camera: Option<Camera>,
+    root: Option<BoardNode>,
+    data: Vec<PerObjectData>,
+}
+
+impl BoardMesh {
+    /// Brings the objects up to date with `game` as `camera` sees it, returns whether they
+    /// changed.
+    pub fn update(&mut self, game: &Game, styles: &[PlayerStyle; 4], camera: &Camera) -> bool {
+        let target_board = game.target_board();
+        let looks = Looks {
+            target_board: &target_board,
+            styles,
+        };
+        match &mut self.root {
+            Some(root)
+                if self.start_version == Some(game.start_version())
+                    && self.styles.as_ref() == Some(styles) =>
+            {
+                let mut changed = false;
+                if self.version != Some(game.version()) {
+                    // the moves both games share are on the board already
+                    let shared = self
+                        .moves
+                        .iter()
+                        .zip(game.moves())
+                        .take_while(|(old, (_, new))| old == new)
+                        .count();
+                    let undone = self.moves[shared..].iter();
+                    let played = game.moves().skip(shared).map(|(_, mv)| mv);
+                    let targets = [&self.target_board, &target_board];
+                    for path in undone.chain(played).chain(targets) {
+                        changed |= root.update_path(game.board(), path, &self.target_board, looks);
+                    }
+                }
+                if !changed && self.camera.as_ref() == Some(camera) {
+                    return false;
+                }
+            }
+            _ => {
+                let root = BoardNode::build(
+                    game.board(),
+                    (0.0, 0.0).into(),
+                    (1.0, 1.0).into(),
+                    CellPath::new(),
+                    false,
+                    looks,
+                );
+                self.root = Some(root);
+            }
+        }
+        self.start_version = Some(game.start_version());
+        self.version = Some(game.version());
+        self.moves = game.moves().map(|(_, mv)| mv.clone()).collect();
+        self.target_board = target_board;
+        self.styles = Some(*styles);
+        self.camera = Some(*camera);
+
+        self.data.clear();
+        if let Some(root) = &self.root {
+            let view = View::new(camera);
+            root.write(Some(&view), NEUTRAL.into(), &mut self.data);
+        }
+        true
+    }
+
+    pub fn data(&self) -> &[PerObjectData] {
+        &self.data
+    }
+}
+
+fn render_state(
+    style: PlayerStyle,
+    position: cgmath::Vector2<f32>,
+    scale: cgmath::Vector2<f32>,
+    brightness: f32,
+    per_object_data: &mut Vec<PerObjectData>,
+) {
+    let color = cgmath::Vector3::from(style.color) * brightness;
+    let outline = match style.symbol {
+        Symbol::Circle => Glyph::Ring,
+        Symbol::Triangle => Glyph::Triangle,
+        Symbol::Square => Glyph::Square,
+        Symbol::Cross => {
+            per_object_data.push(PerObjectData {
+                object_position: position,
+                rotation: cgmath::Rad::from(cgmath::Deg(45.0)).0,
+                scale: cgmath::vec2(0.1 * scale.x, scale.y),
+                color,
+                glyph: Glyph::Filled as u32,
+                line_width: 0.0,
+            });
+            per_object_data.push(PerObjectData {
+                object_position: position,
+                rotation: cgmath::Rad::from(cgmath::Deg(-45.0)).0,
+                scale: cgmath::vec2(0.1 * scale.x, scale.y),
+                color,
+                glyph: Glyph::Filled as u32,
+                line_width: 0.0,
+            });
+            return;
+        }
+    };
+    per_object_data.push(PerObjectData {
+        object_position: position,
+        rotation: cgmath::Rad::from(cgmath::Deg(0.0)).0,
+        scale,
+        color,
+        glyph: outline as u32,
+        line_width: 0.1,
+    });
+}
+
+/// Colors the cells the engine looked at, brighter the more playouts went through them.
+pub fn render_heatmap(stats: &[MoveStats], size: usize, per_object_data: &mut Vec<PerObjectData>) {
+    let max_visits = stats.iter().map(|stats| stats.visits).max().unwrap_or(0);
+    for stats in stats {
+        let heat = stats.visits as f32 / max_visits as f32;
+        let (position, scale) = stats.mv.world_rect(size);
+        per_object_data.push(PerObjectData {
+            object_position: position,
+            rotation: cgmath::Rad::from(cgmath::Deg(0.0)).0,
+            scale: scale * 0.8,
+            color: cgmath::vec3(0.8, 0.5, 0.0) * heat,
+            glyph: Glyph::Filled as u32,
+            line_width: 0.0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_styles, mcts::Rng};
+
+    fn render(
+        board: &Board,
+        target_board: &CellPath,
+        camera: Option<&Camera>,
+    ) -> Vec<PerObjectData> {
+        let mut data = vec![];
+        render_board(board, target_board, &default_styles(), camera, &mut data);
+        data
+    }
+
+    #[test]
+    fn grids_are_drawn_once() {
+        let game = Game::new(1);
+        assert_eq!(render(game.board(), &CellPath::new(), None).len(), 8);
+        let game = Game::new(2);
+        assert_eq!(
+            render(game.board(), &CellPath::new(), None).len(),
+            8 + 9 * 8
+        );
+    }
+
+    #[test]
+    fn cameras_leave_out_what_they_cant_show() {
+        let game = Game::new(4);
+        let all = render(game.board(), &CellPath::new(), None).len();
+
+        // a corner of the bottom left board
+        let mut camera = Camera::fit(game.board(), 800.0, 600.0);
+        camera.position = (-1.4, -1.4).into();
+        camera.scale = 20.0;
+        let corner = render(game.board(), &CellPath::new(), Some(&camera)).len();
+        assert!(corner < all / 50, "{corner} of {all}");
+
+        // the boards two layers down are only a few pixels big
+        let camera = Camera::fit(game.board(), 40.0, 40.0);
+        let tiny = render(game.board(), &CellPath::new(), Some(&camera));
+        assert_eq!(tiny.len(), 8 + 9 * (8 + 9));
+        let tile = tiny[0];
+        assert_eq!({ tile.scale }, cgmath::vec2(1.0 / 3.0, 1.0 / 3.0));
+        assert_eq!({ tile.color }, cgmath::Vector3::from(NEUTRAL));
+    }
+
+    fn check(mesh: &BoardMesh, game: &Game, camera: &Camera) {
+        let expected = render(game.board(), &game.target_board(), Some(camera));
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(mesh.data()),
+            bytemuck::cast_slice::<_, u8>(&expected)
+        );
+    }
+
+    #[test]
+    fn mesh_follows_the_game() {
+        let styles = default_styles();
+        let mut camera = Camera::fit(&Board::default(), 800.0, 600.0);
+        let mut rng = Rng::new(7);
+        let mut game = Game::new(3);
+        let mut mesh = BoardMesh::default();
+        assert!(mesh.update(&game, &styles, &camera));
+        for _ in 0..60 {
+            if game.outcome().is_some() || rng.below(4) == 0 {
+                game.undo();
+            } else {
+                let moves = game.legal_moves();
+                game.play(&moves[rng.below(moves.len())]).unwrap();
+            }
+            assert!(mesh.update(&game, &styles, &camera));
+            assert!(!mesh.update(&game, &styles, &camera));
+            check(&mesh, &game, &camera);
+        }
+
+        // many moves at once, and a game that starts over on the same board
+        let num_moves = game.num_moves();
+        game.jump_to(num_moves / 2);
+        assert!(mesh.update(&game, &styles, &camera));
+        check(&mesh, &game, &camera);
+        game.jump_to(num_moves);
+        assert!(mesh.update(&game, &styles, &camera));
+        check(&mesh, &game, &camera);
+        let mut game = Game::new(3);
+        game.play(&"5.5.5".parse().unwrap()).unwrap();
+        assert!(mesh.update(&game, &styles, &camera));
+        check(&mesh, &game, &camera);
+
+        camera.scale *= 2.0;
+        assert!(mesh.update(&game, &styles, &camera));
+        let mut styles = styles;
+        styles[0].color = [1.0, 1.0, 1.0];
+        assert!(mesh.update(&game, &styles, &camera));
+    }
+}
@@ -0,0 +1,441 @@
+use std::io::Write;
+use std::time::Duration;
+
+use crate::{
+    AlphaBeta, BoardShape, CellPath, Game, Mcts, Move, MoveError, ParseCellPathError,
+    ParsePositionError, ParseRulesError, Position, Rules, SearchBudget,
+};
+
+/// The game engine driven by text commands, one per line, so bots written in any language can
+/// play without the ui.
+///
+/// Like UCI for chess, the caller sends commands and the server answers with lines of its own:
+///
+/// ```text
+/// uttt                      -> id name ..., protocol 1, utttok
+/// isready                   -> readyok
+/// newgame [layers <n>] [size <n>] [winlength <n>] [rules <rules>]
+/// position startpos|fen <position> [moves <move>...]
+/// move <move>...
+/// go [alphabeta|mcts] [depth <n>] [nodes <n>] [movetime <ms>] [playouts <n>] [threads <n>]
+///    [seed <n>]             -> info ..., bestmove <move>|none
+/// legal                     -> legal <move>...
+/// show                      -> position <position>, turn <player>
+/// result                    -> result <result>
+/// quit
+/// ```
+///
+/// `newgame` remembers the settings for `position startpos`, and leaves out ones that are not
+/// given at their defaults. `go` only searches, the move has to be played with `move`. It uses
+/// alpha-beta unless told otherwise, except in games of more than two players, which only
+/// `mcts` can play. Results
+/// are written like in a [`GameRecord`](crate::GameRecord), `*` while the game is running. A
+/// command that can't be carried out is answered with `error` and a reason, and changes nothing.
+#[derive(Debug, Clone)]
+pub struct Server {
+    num_layers: usize,
+    shape: BoardShape,
+    rules: Rules,
+    game: Game,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self {
+            num_layers: 2,
+            shape: BoardShape::default(),
+            rules: Rules::default(),
+            game: Game::new(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    UnknownCommand(String),
+    InvalidArgument(String),
+    /// The board size is 0 or the number of cells in a row needed to win does not fit on it.
+    InvalidShape,
+    InvalidRules(ParseRulesError),
+    InvalidPosition(ParsePositionError),
+    InvalidMove(ParseCellPathError),
+    IllegalMove(Move, MoveError),
+    /// Alpha-beta was asked to search a game of more than two players.
+    TwoPlayerEngine,
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::UnknownCommand(command) => write!(f, "unknown command \"{command}\""),
+            CommandError::InvalidArgument(argument) => {
+                write!(f, "invalid argument \"{argument}\"")
+            }
+            CommandError::InvalidShape => write!(f, "the board size or win length is invalid"),
+            CommandError::InvalidRules(error) => write!(f, "{error}"),
+            CommandError::InvalidPosition(error) => write!(f, "{error}"),
+            CommandError::InvalidMove(error) => write!(f, "{error}"),
+            CommandError::IllegalMove(mv, error) => write!(f, "move {mv} is illegal: {error}"),
+            CommandError::TwoPlayerEngine => {
+                write!(f, "alphabeta only plays games of two players")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// The value after the name of an argument.
+fn value<'a, T: std::str::FromStr>(
+    name: &str,
+    words: &mut impl Iterator<Item = &'a str>,
+) -> Result<T, CommandError> {
+    words
+        .next()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| CommandError::InvalidArgument(name.to_string()))
+}
+
+impl Server {
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Carries out one command and writes the answer to `out`, returning false once the server
+    /// should quit.
+    pub fn handle(&mut self, line: &str, out: &mut impl Write) -> std::io::Result<bool> {
+        match self.run(line, out) {
+            Ok(keep_going) => Ok(keep_going),
+            Err(CommandErrorOrIo::Io(error)) => Err(error),
+            Err(CommandErrorOrIo::Command(error)) => {
+                writeln!(out, "error {error}")?;
+                out.flush()?;
+                Ok(true)
+            }
+        }
+    }
+
+    fn run(&mut self, line: &str, out: &mut impl Write) -> Result<bool, CommandErrorOrIo> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None => {}
+            Some("uttt") => {
+                writeln!(
+                    out,
+                    "id name Ultimate Tic Tac Toe {}",
+                    env!("CARGO_PKG_VERSION")
+                )?;
+                writeln!(out, "protocol 1")?;
+                writeln!(out, "utttok")?;
+            }
+            Some("isready") => writeln!(out, "readyok")?,
+            Some("newgame") => self.new_game(words)?,
+            Some("position") => self.set_position(words)?,
+            Some("move") => {
+                let mut game = self.game.clone();
+                play_moves(&mut game, words)?;
+                self.game = game;
+            }
+            Some("go") if self.game.outcome().is_some() => writeln!(out, "bestmove none")?,
+            Some("go") => {
+                let mv = self.go(words, out)?;
+                match mv {
+                    Some(mv) => writeln!(out, "bestmove {}", mv.display(self.game.shape().size))?,
+                    None => writeln!(out, "bestmove none")?,
+                }
+            }
+            Some("legal") => {
+                write!(out, "legal")?;
+                let size = self.game.shape().size;
+                for mv in self.game.legal_moves() {
+                    write!(out, " {}", mv.display(size))?;
+                }
+                writeln!(out)?;
+            }
+            Some("show") => {
+                writeln!(out, "position {}", self.game.position())?;
+                writeln!(out, "turn {}", self.game.current_player())?;
+            }
+            Some("result") => writeln!(
+                out,
+                "result {}",
+                crate::record::result_to_string(self.game.outcome())
+            )?,
+            Some("quit") => return Ok(false),
+            Some(command) => {
+                return Err(CommandError::UnknownCommand(command.to_string()).into());
+            }
+        }
+        out.flush()?;
+        Ok(true)
+    }
+
+    fn new_game<'a>(
+        &mut self,
+        mut words: impl Iterator<Item = &'a str>,
+    ) -> Result<(), CommandError> {
+        let mut num_layers = 2;
+        let mut shape = BoardShape::default();
+        let mut rules = Rules::default();
+        while let Some(word) = words.next() {
+            match word {
+                "layers" => num_layers = value(word, &mut words)?,
+                "size" => shape.size = value(word, &mut words)?,
+                "winlength" => shape.win_length = value(word, &mut words)?,
+                "rules" => {
+                    // the rules are the rest of the line, they contain spaces
+                    let rest = words.by_ref().collect::<Vec<_>>().join(" ");
+                    rules = rest.parse().map_err(CommandError::InvalidRules)?;
+                }
+                _ => return Err(CommandError::InvalidArgument(word.to_string())),
+            }
+        }
+        if num_layers == 0 {
+            return Err(CommandError::InvalidArgument("layers".to_string()));
+        }
+        if !(1..=shape.size).contains(&shape.win_length) {
+            return Err(CommandError::InvalidShape);
+        }
+        if !shape.fits(num_layers) {
+            let argument = if num_layers > BoardShape::MAX_LAYERS {
+                "layers"
+            } else {
+                "size"
+            };
+            return Err(CommandError::InvalidArgument(argument.to_string()));
+        }
+
+        self.num_layers = num_layers;
+        self.shape = shape;
+        self.rules = rules;
+        self.game = self.start_game();
+        Ok(())
+    }
+
+    fn start_game(&self) -> Game {
+        let mut game = Game::with_shape(self.num_layers, self.shape);
+        game.set_rules(self.rules);
+        game
+    }
+
+    fn set_position<'a>(
+        &mut self,
+        words: impl Iterator<Item = &'a str>,
+    ) -> Result<(), CommandError> {
+        // positions contain spaces, so they go on until the moves
+        let words: Vec<_> = words.collect();
+        let (setup, moves) = match words.iter().position(|&word| word == "moves") {
+            Some(index) => (&words[..index], &words[index + 1..]),
+            None => (&words[..], &[][..]),
+        };
+        let mut game = match setup {
+            ["startpos"] => self.start_game(),
+            ["fen", fields @ ..] => {
+                let position: Position = fields
+                    .join(" ")
+                    .parse()
+                    .map_err(CommandError::InvalidPosition)?;
+                position
+                    .check_rules(self.rules)
+                    .map_err(CommandError::InvalidPosition)?;
+                let mut game = Game::from_position(position);
+                game.set_rules(self.rules);
+                game
+            }
+            _ => return Err(CommandError::InvalidArgument(setup.join(" "))),
+        };
+        play_moves(&mut game, moves.iter().copied())?;
+        self.game = game;
+        Ok(())
+    }
+
+    fn go<'a>(
+        &self,
+        mut words: impl Iterator<Item = &'a str>,
+        out: &mut impl Write,
+    ) -> Result<Option<Move>, CommandErrorOrIo> {
+        let mut use_mcts = self.game.rules().num_players > 2;
+        let mut alpha_beta = AlphaBeta::default();
+        let mut mcts = Mcts {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            ..Default::default()
+        };
+        while let Some(word) = words.next() {
+            match word {
+                "alphabeta" => use_mcts = false,
+                "mcts" => use_mcts = true,
+                "depth" => alpha_beta.max_depth = value(word, &mut words)?,
+                "nodes" => alpha_beta.budget = SearchBudget::Nodes(value(word, &mut words)?),
+                "movetime" => {
+                    alpha_beta.budget =
+                        SearchBudget::Time(Duration::from_millis(value(word, &mut words)?))
+                }
+                "playouts" => mcts.playouts = value(word, &mut words)?,
+                "threads" => mcts.threads = value(word, &mut words)?,
+                "seed" => mcts.seed = value(word, &mut words)?,
+                _ => return Err(CommandError::InvalidArgument(word.to_string()).into()),
+            }
+        }
+
+        // a search without playouts or threads finds no move
+        for (name, amount) in [
+            ("playouts", mcts.playouts),
+            ("threads", mcts.threads as u64),
+        ] {
+            if use_mcts && amount == 0 {
+                return Err(CommandError::InvalidArgument(name.to_string()).into());
+            }
+        }
+        if !use_mcts && self.game.rules().num_players > 2 {
+            return Err(CommandError::TwoPlayerEngine.into());
+        }
+
+        if use_mcts {
+            let result = mcts.search(&self.game);
+            if let Some(stats) = result
+                .stats
+                .iter()
+                .find(|stats| Some(&stats.mv) == result.best_move.as_ref())
+            {
+                writeln!(
+                    out,
+                    "info playouts {} visits {} winrate {:.3}",
+                    mcts.playouts,
+                    stats.visits,
+                    stats.score / stats.visits.max(1) as f64
+                )?;
+            }
+            Ok(result.best_move)
+        } else {
+            let result = alpha_beta.search(&self.game);
+            writeln!(
+                out,
+                "info depth {} score {} nodes {}",
+                result.depth, result.score, result.nodes
+            )?;
+            Ok(result.best_move)
+        }
+    }
+}
+
+fn play_moves<'a>(
+    game: &mut Game,
+    words: impl Iterator<Item = &'a str>,
+) -> Result<(), CommandError> {
+    for word in words {
+        let mv =
+            CellPath::parse_sized(word, game.shape().size).map_err(CommandError::InvalidMove)?;
+        game.play(&mv)
+            .map_err(|error| CommandError::IllegalMove(mv, error))?;
+    }
+    Ok(())
+}
+
+/// Errors of commands are answered, while errors writing the answer end the server.
+enum CommandErrorOrIo {
+    Command(CommandError),
+    Io(std::io::Error),
+}
+
+impl From<CommandError> for CommandErrorOrIo {
+    fn from(error: CommandError) -> Self {
+        CommandErrorOrIo::Command(error)
+    }
+}
+
+impl From<std::io::Error> for CommandErrorOrIo {
+    fn from(error: std::io::Error) -> Self {
+        CommandErrorOrIo::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the commands and returns everything the server answered.
+    fn run(server: &mut Server, commands: &str) -> String {
+        let mut out = vec![];
+        for line in commands.lines() {
+            server.handle(line, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plays_a_game() {
+        let mut server = Server::default();
+        assert!(run(&mut server, "uttt\nisready").ends_with("utttok\nreadyok\n"));
+
+        let answer = run(
+            &mut server,
+            "newgame layers 1\nmove 1 4 2 5\ngo depth 3 nodes 100000\nresult",
+        );
+        assert!(answer.contains("bestmove 3\n"), "{answer}");
+        assert!(answer.ends_with("result *\n"));
+        assert_eq!(run(&mut server, "move 3\nresult"), "result 1-0\n");
+        assert_eq!(run(&mut server, "go"), "bestmove none\n");
+    }
+
+    #[test]
+    fn sets_up_positions() {
+        let mut server = Server::default();
+        let answer = run(
+            &mut server,
+            "newgame layers 1 size 4 rules misere\nposition startpos moves a1\nshow\nlegal",
+        );
+        assert_eq!(server.game().rules(), "misere".parse().unwrap());
+        assert!(answer.starts_with("position "), "{answer}");
+        assert_eq!(answer.lines().nth(1), Some("turn Cross"));
+        assert_eq!(answer.lines().nth(2).unwrap().split(' ').count(), 16);
+
+        run(&mut server, "newgame\nposition fen oxo/xox/3 o - moves 7");
+        assert_eq!(run(&mut server, "result"), "result 1-0\n");
+    }
+
+    #[test]
+    fn more_players_need_mcts() {
+        let mut server = Server::default();
+        let answer = run(
+            &mut server,
+            "newgame layers 1 rules players=3\ngo playouts 50\ngo alphabeta depth 2",
+        );
+        let lines: Vec<_> = answer.lines().collect();
+        assert!(lines[1].starts_with("bestmove "), "{answer}");
+        assert_eq!(lines[2], "error alphabeta only plays games of two players");
+    }
+
+    #[test]
+    fn searches_need_playouts_and_threads() {
+        let mut server = Server::default();
+        assert_eq!(
+            run(&mut server, "go mcts playouts 0\ngo mcts threads 0"),
+            "error invalid argument \"playouts\"\nerror invalid argument \"threads\"\n"
+        );
+        let answer = run(&mut server, "go mcts playouts 20 threads 1");
+        assert!(
+            answer.lines().last().unwrap().starts_with("bestmove "),
+            "{answer}"
+        );
+        assert!(!answer.contains("none"), "{answer}");
+    }
+
+    #[test]
+    fn errors_change_nothing() {
+        let mut server = Server::default();
+        let answer = run(
+            &mut server,
+            "move 5.5 5.5\nfly\nnewgame layers 0\nposition fen x\ngo depth\nnewgame winlength 4\n\
+             newgame layers 20\nnewgame size 1000\nposition fen 3/3/3 t -",
+        );
+        assert_eq!(answer.lines().count(), 9);
+        assert!(
+            answer.lines().all(|line| line.starts_with("error ")),
+            "{answer}"
+        );
+        assert_eq!(server.game().num_moves(), 0);
+        assert_eq!(server.game().num_layers(), 2);
+        let mut out = vec![];
+        assert!(!server.handle("quit", &mut out).unwrap());
+    }
+}
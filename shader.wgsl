@@ -3,19 +3,21 @@ struct VertexInput {
     @location(1) rotation: f32,
     @location(2) scale: vec2<f32>,
     @location(3) color: vec3<f32>,
-    @location(4) is_circle: u32,
-    @location(5) circle_width: f32,
+    @location(4) glyph: u32,
+    @location(5) line_width: f32,
     @location(6) position: vec2<f32>,
     @location(7) tex_coord: vec2<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @interpolate(flat) @location(0) is_circle: u32,
-    @interpolate(flat) @location(1) circle_width: f32,
+    @interpolate(flat) @location(0) glyph: u32,
+    @interpolate(flat) @location(1) line_width: f32,
     @location(2) position: vec2<f32>,
-    @location(3) tex_coord: vec2<f32>,
+    // goes from -1 to 1 across the object, and a bit further on the margin around it.
+    @location(3) uv: vec2<f32>,
     @location(4) color: vec3<f32>,
+    @interpolate(flat) @location(5) alpha: f32,
 };
 
 struct Camera {
@@ -34,11 +36,20 @@ fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     let aspect = camera.screen_size.x / camera.screen_size.y;
+    let pixels_per_unit = camera.scale * camera.screen_size.y / 2.0;
+
+    // objects thinner than a pixel are drawn a pixel wide and fainter instead, so that they
+    // don't flicker as they move across pixels
+    let size_pixels = model.scale * pixels_per_unit;
+    let shown_pixels = max(size_pixels, vec2<f32>(1.0));
+    // with a pixel on every side for the edges to fade out in
+    let quad_pixels = shown_pixels + 2.0;
 
     var out: VertexOutput;
-    out.is_circle = model.is_circle;
-    out.circle_width = model.circle_width;
-    out.position = model.position * model.scale;
+    out.glyph = model.glyph;
+    out.line_width = model.line_width;
+    out.alpha = size_pixels.x / shown_pixels.x * size_pixels.y / shown_pixels.y;
+    out.position = model.position * quad_pixels / pixels_per_unit;
     out.position = vec2<f32>(
         out.position.x * cos(-model.rotation) - out.position.y * sin(-model.rotation),
         out.position.y * cos(-model.rotation) + out.position.x * sin(-model.rotation),
@@ -51,18 +62,48 @@ fn vs_main(
         out.clip_position.z,
         out.clip_position.w,
     );
-    out.tex_coord = model.tex_coord;
+    out.uv = (model.tex_coord * 2.0 - 1.0) * quad_pixels / shown_pixels;
     out.color = model.color;
     return out;
 }
 
+// signed distance to an equilateral triangle pointing up with sides of 2 * r, centered on the
+// origin, from https://iquilezles.org/articles/distfunctions2d/
+fn triangle_distance(p: vec2<f32>, r: f32) -> f32 {
+    let k = sqrt(3.0);
+    var q = vec2<f32>(abs(p.x) - r, p.y + r / k);
+    if q.x + k * q.y > 0.0 {
+        q = vec2<f32>(q.x - k * q.y, -k * q.x - q.y) / 2.0;
+    }
+    q.x -= clamp(q.x, -2.0 * r, 0.0);
+    return -length(q) * sign(q.y);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let uv = in.tex_coord * 2.0 - 1.0;
+    let uv = in.uv;
+
+    // how far outside of the outline the fragment is, the glyph types match Glyph
+    var outside = max(abs(uv.x), abs(uv.y)) - 1.0;
+    switch in.glyph {
+        case 1u: {
+            outside = abs(length(uv) - (1.0 - in.line_width * 2.0)) - in.line_width;
+        }
+        case 2u: {
+            outside = abs(triangle_distance(uv + vec2<f32>(0.0, 0.23), 0.8) + in.line_width) - in.line_width;
+        }
+        case 3u: {
+            outside = abs(max(abs(uv.x), abs(uv.y)) - (1.0 - in.line_width * 2.0)) - in.line_width;
+        }
+        default: {}
+    }
 
-    if in.is_circle != 0u && abs(length(uv) - (1.0 - in.circle_width * 2.0)) > in.circle_width {
+    // how much of the pixel the glyph covers, from the distance in pixels, smooths the edges
+    let coverage = clamp(0.5 - outside / max(fwidth(outside), 1e-6), 0.0, 1.0) * in.alpha;
+    if coverage <= 0.0 {
         discard;
     }
 
-    return vec4<f32>(in.color, 1.0);
+    // with premultiplied alpha
+    return vec4<f32>(in.color * coverage, coverage);
 }
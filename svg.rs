@@ -0,0 +1,176 @@
+use std::fmt::Write;
+
+use crate::{render_board, Board, CellPath, Glyph, PerObjectData, PlayerStyle};
+
+/// The color around and between the boards, the same as in pictures of the
+/// [`OffscreenRenderer`](crate::OffscreenRenderer).
+const BACKGROUND: [f32; 3] = [0.02, 0.02, 0.02];
+
+fn color(color: impl Into<[f32; 3]>) -> String {
+    let [r, g, b] = color
+        .into()
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Writes `object` as the shape the shader cuts out of its quad.
+///
+/// Outlines are drawn in the texture coordinates of the shader, from -1 to 1 across the quad, so
+/// their line widths are the same as on screen.
+fn write_object(svg: &mut String, object: &PerObjectData) -> std::fmt::Result {
+    let position = object.object_position;
+    let scale = object.scale;
+    let fill = color(object.color);
+    let line_width = object.line_width;
+    // the shader turns objects the other way
+    let mut transform = format!("translate({} {})", position.x, position.y);
+    if object.rotation != 0.0 {
+        write!(transform, " rotate({})", -object.rotation.to_degrees())?;
+    }
+
+    if object.glyph == Glyph::Filled as u32 {
+        return writeln!(
+            svg,
+            r#"<rect transform="{transform}" x="{}" y="{}" width="{}" height="{}" fill="{fill}"/>"#,
+            -scale.x / 2.0,
+            -scale.y / 2.0,
+            scale.x,
+            scale.y,
+        );
+    }
+
+    write!(transform, " scale({} {})", scale.x / 2.0, scale.y / 2.0)?;
+    let stroke = format!(
+        r#"fill="none" stroke="{fill}" stroke-width="{}""#,
+        line_width * 2.0
+    );
+    // the middle of the outline, which lies inside the shape by half of its width
+    let inset = 1.0 - line_width * 2.0;
+    if object.glyph == Glyph::Ring as u32 {
+        writeln!(
+            svg,
+            r#"<circle transform="{transform}" r="{inset}" {stroke}/>"#
+        )
+    } else if object.glyph == Glyph::Square as u32 {
+        writeln!(
+            svg,
+            r#"<rect transform="{transform}" x="{}" y="{}" width="{}" height="{}" {stroke}/>"#,
+            -inset,
+            -inset,
+            inset * 2.0,
+            inset * 2.0,
+        )
+    } else {
+        // the triangle of the shader has sides of 1.6 and its center 0.23 below the middle
+        let k = 3f32.sqrt();
+        let r = 0.8 * (1.0 - line_width * k / 0.8);
+        let points = [(-r, -r / k), (r, -r / k), (0.0, 2.0 * r / k)]
+            .map(|(x, y)| format!("{x},{}", y - 0.23))
+            .join(" ");
+        writeln!(
+            svg,
+            r#"<polygon transform="{transform}" points="{points}" {stroke}/>"#
+        )
+    }
+}
+
+/// Turns the objects the renderer would draw into an SVG image of the world between `min` and
+/// `max`, which stays sharp at any zoom.
+pub fn objects_to_svg(
+    data: &[PerObjectData],
+    min: cgmath::Vector2<f32>,
+    max: cgmath::Vector2<f32>,
+    width: u32,
+    height: u32,
+) -> String {
+    let size = max - min;
+    let mut svg = String::new();
+    // writing to a String cannot fail
+    let _ = writeln!(
+        svg,
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}""#,
+            r#" viewBox="{} {} {} {}">"#
+        ),
+        width, height, min.x, -max.y, size.x, size.y,
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+        min.x,
+        -max.y,
+        size.x,
+        size.y,
+        color(BACKGROUND)
+    );
+    // the y axis of the world points up
+    svg.push_str("<g transform=\"scale(1 -1)\" stroke-linejoin=\"miter\">\n");
+    for object in data {
+        let _ = write_object(&mut svg, object);
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// Draws the whole of `board` like the window does as an SVG image, with the board at
+/// `target_board` highlighted.
+pub fn board_to_svg(
+    board: &Board,
+    target_board: &CellPath,
+    styles: &[PlayerStyle; 4],
+    width: u32,
+) -> String {
+    let mut data = vec![];
+    // vector images can be zoomed into, so everything is drawn
+    render_board(board, target_board, styles, None, &mut data);
+    // the same margin as pictures of the offscreen renderer
+    let half_size = board.shape().size as f32 / 1.9;
+    objects_to_svg(
+        &data,
+        (-half_size, -half_size).into(),
+        (half_size, half_size).into(),
+        width,
+        width,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_styles, Game};
+
+    #[test]
+    fn objects_become_shapes() {
+        let mut game = Game::new(1);
+        game.set_rules("players=4".parse().unwrap());
+        for mv in ["1", "2", "3", "4"] {
+            game.play(&mv.parse().unwrap()).unwrap();
+        }
+        let svg = board_to_svg(game.board(), &game.target_board(), &default_styles(), 512);
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"512\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert_eq!(svg.matches("<polygon").count(), 1);
+        // the bars of the cross are turned both ways
+        assert_eq!(svg.matches(" rotate(-45)").count(), 1);
+        assert_eq!(svg.matches(" rotate(45)").count(), 1);
+        assert!(svg.contains(r##"stroke="#0000ff" stroke-width="0.2""##));
+        let mut data = vec![];
+        render_board(
+            game.board(),
+            &game.target_board(),
+            &default_styles(),
+            None,
+            &mut data,
+        );
+        // one element for every object, and the background
+        assert_eq!(svg.lines().count(), data.len() + 5);
+    }
+
+    #[test]
+    fn colors_are_clamped() {
+        assert_eq!(color([0.0, 0.5, 1.0]), "#0080ff");
+        assert_eq!(color([-1.0, 2.0, 0.2]), "#00ff33");
+    }
+}
@@ -0,0 +1,285 @@
+use std::time::{Duration, Instant};
+
+use crate::{mcts::Rng, today, BoardShape, Engine, Game, GameRecord, Outcome, Rules, State};
+
+/// Games between two engines to see which one is stronger.
+///
+/// The engines take turns at starting, since Circle always moves first, and every pair of games
+/// is played from the same opening of random moves with the engines swapped, so that neither of
+/// them profits from a lucky opening. The pairs go through the layer counts in order.
+#[derive(Debug, Clone)]
+pub struct Tournament {
+    pub engines: [Engine; 2],
+    pub num_games: usize,
+    pub layers: Vec<usize>,
+    pub shape: BoardShape,
+    pub rules: Rules,
+    /// The number of random moves at the start of every game.
+    pub opening_moves: usize,
+    pub seed: u64,
+}
+
+impl Default for Tournament {
+    fn default() -> Self {
+        Self {
+            engines: [Engine::AlphaBeta(Default::default()); 2],
+            num_games: 10,
+            layers: vec![2],
+            shape: BoardShape::default(),
+            rules: Rules::default(),
+            opening_moves: 2,
+            seed: 0,
+        }
+    }
+}
+
+/// One game of a [`Tournament`].
+#[derive(Debug, Clone)]
+pub struct TournamentGame {
+    /// The index of the engine that played Circle.
+    pub first: usize,
+    pub outcome: Option<Outcome>,
+    pub num_moves: usize,
+    /// The time each engine spent thinking, indexed like [`Tournament::engines`].
+    pub think_time: [Duration; 2],
+    /// The moves each engine chose, without the opening.
+    pub engine_moves: [usize; 2],
+    pub record: GameRecord,
+}
+
+impl TournamentGame {
+    /// The engine that won, `None` for a stalemate.
+    pub fn winner(&self) -> Option<usize> {
+        match self.outcome {
+            Some(Outcome::Winner(State::Circle)) => Some(self.first),
+            Some(Outcome::Winner(_)) => Some(1 - self.first),
+            _ => None,
+        }
+    }
+}
+
+/// The results of a [`Tournament`] from the point of view of the first engine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Standings {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub total_moves: usize,
+    pub think_time: [Duration; 2],
+    pub engine_moves: [usize; 2],
+}
+
+impl Standings {
+    pub fn add(&mut self, game: &TournamentGame) {
+        match game.winner() {
+            Some(0) => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+        self.total_moves += game.num_moves;
+        for engine in 0..2 {
+            self.think_time[engine] += game.think_time[engine];
+            self.engine_moves[engine] += game.engine_moves[engine];
+        }
+    }
+
+    pub fn num_games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The points of the first engine per game, where a draw counts a half.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.num_games().max(1) as f64
+    }
+
+    pub fn average_length(&self) -> f64 {
+        self.total_moves as f64 / self.num_games().max(1) as f64
+    }
+
+    /// The average time `engine` took for a move.
+    pub fn time_per_move(&self, engine: usize) -> Duration {
+        self.think_time[engine] / self.engine_moves[engine].max(1) as u32
+    }
+
+    /// How much stronger the first engine is in Elo, with the margin of the 95% confidence
+    /// interval. Both are infinite while one of the engines has not scored at all, and the margin
+    /// is infinite before any game was played.
+    pub fn elo_difference(&self) -> (f64, f64) {
+        let n = self.num_games() as f64;
+        let score = self.score();
+        if n == 0.0 {
+            return (0.0, f64::INFINITY);
+        }
+        if score <= 0.0 || score >= 1.0 {
+            return (elo(score), f64::INFINITY);
+        }
+        // the spread of the points of single games around the average
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / n;
+        let margin = 1.96 * (variance / n).sqrt();
+        let low = elo(score - margin);
+        let high = elo(score + margin);
+        (elo(score), (high - low) / 2.0)
+    }
+}
+
+/// The Elo difference that gives the stronger side `score` points per game.
+fn elo(score: f64) -> f64 {
+    if score <= 0.0 {
+        f64::NEG_INFINITY
+    } else if score >= 1.0 {
+        f64::INFINITY
+    } else {
+        -400.0 * (1.0 / score - 1.0).log10()
+    }
+}
+
+impl Tournament {
+    /// Plays all games, handing each one to `on_game` as soon as it is over.
+    pub fn run(&self, mut on_game: impl FnMut(usize, &TournamentGame)) -> Standings {
+        assert!(!self.layers.is_empty());
+        assert_eq!(self.rules.num_players, 2, "tournaments are for two engines");
+
+        let mut standings = Standings::default();
+        let mut rng = Rng::new(self.seed);
+        let mut opening = Game::new(1);
+        for index in 0..self.num_games {
+            if index % 2 == 0 {
+                let num_layers = self.layers[index / 2 % self.layers.len()];
+                opening = self.opening(num_layers, &mut rng);
+            }
+            let game = self.play(&opening, index % 2, index + 1);
+            standings.add(&game);
+            on_game(index, &game);
+        }
+        standings
+    }
+
+    fn opening(&self, num_layers: usize, rng: &mut Rng) -> Game {
+        let mut game = Game::with_shape(num_layers, self.shape);
+        game.set_rules(self.rules);
+        for _ in 0..self.opening_moves {
+            let moves = game.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            game.play(&moves[rng.below(moves.len())]).unwrap();
+        }
+        game
+    }
+
+    /// Plays one game on from `opening`, where the engine with index `first` plays Circle.
+    fn play(&self, opening: &Game, first: usize, round: usize) -> TournamentGame {
+        let mut game = opening.clone();
+        let mut think_time = [Duration::ZERO; 2];
+        let mut engine_moves = [0; 2];
+        while game.outcome().is_none() {
+            let engine = if game.current_player() == State::Circle {
+                first
+            } else {
+                1 - first
+            };
+            let start = Instant::now();
+            let mv = self.engines[engine]
+                .choose_move(&game)
+                .expect("games that are not over have moves");
+            think_time[engine] += start.elapsed();
+            engine_moves[engine] += 1;
+            game.play(&mv).unwrap();
+        }
+
+        let mut record = GameRecord::from_game(&game);
+        record.set_tag("Event", "Tournament");
+        record.set_tag("Round", round);
+        record.set_tag("Circle", self.engines[first]);
+        record.set_tag("Cross", self.engines[1 - first]);
+        record.set_tag("Date", today());
+        TournamentGame {
+            first,
+            outcome: game.outcome(),
+            num_moves: game.num_moves(),
+            think_time,
+            engine_moves,
+            record,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AlphaBeta, SearchBudget};
+
+    #[test]
+    fn engines_take_turns_at_starting() {
+        let tournament = Tournament {
+            engines: [
+                Engine::AlphaBeta(AlphaBeta {
+                    budget: SearchBudget::Nodes(500),
+                    max_depth: 2,
+                }),
+                "mcts:playouts=20".parse().unwrap(),
+            ],
+            num_games: 4,
+            layers: vec![1, 2],
+            opening_moves: 1,
+            ..Default::default()
+        };
+        let mut games = vec![];
+        let standings = tournament.run(|_, game| games.push(game.clone()));
+
+        assert_eq!(standings.num_games(), 4);
+        let firsts: Vec<_> = games.iter().map(|game| game.first).collect();
+        assert_eq!(firsts, [0, 1, 0, 1]);
+        let layers: Vec<_> = games
+            .iter()
+            .map(|game| game.record.replay().unwrap().num_layers())
+            .collect();
+        assert_eq!(layers, [1, 1, 2, 2]);
+        // both games of a pair start with the same opening
+        assert_eq!(games[2].record.moves[0], games[3].record.moves[0]);
+        assert_eq!(games[1].record.tag("Circle"), Some("mcts:playouts=20"));
+        assert_eq!(
+            standings.total_moves,
+            games.iter().map(|game| game.num_moves).sum::<usize>()
+        );
+        assert_eq!(
+            standings.engine_moves.iter().sum::<usize>(),
+            standings.total_moves - 4
+        );
+    }
+
+    #[test]
+    fn elo_from_scores() {
+        let even = Standings {
+            wins: 10,
+            draws: 0,
+            losses: 10,
+            ..Default::default()
+        };
+        let (difference, margin) = even.elo_difference();
+        assert!(difference.abs() < 1e-9);
+        assert!((margin - 160.0).abs() < 10.0, "{margin}");
+
+        let ahead = Standings {
+            wins: 30,
+            draws: 40,
+            losses: 10,
+            ..Default::default()
+        };
+        let (difference, _) = ahead.elo_difference();
+        // a score of 0.625
+        assert!((difference - 88.7).abs() < 0.1, "{difference}");
+
+        let sweep = Standings {
+            wins: 5,
+            ..Default::default()
+        };
+        assert_eq!(sweep.elo_difference(), (f64::INFINITY, f64::INFINITY));
+        let (difference, margin) = Standings::default().elo_difference();
+        assert_eq!(difference, 0.0);
+        assert_eq!(margin, f64::INFINITY);
+    }
+}